@@ -0,0 +1,170 @@
+//! Runs the server without Shuttle, e.g. under systemd or in a container.
+//!
+//! Configuration is read from CLI flags, falling back to environment variables:
+//!   --bind <addr>          BIND_ADDR      (default: 0.0.0.0:8000)
+//!   --store <backend>      ORDER_STORE    (postgres or memory; default: postgres if a database URL is set)
+//!   --database-url <url>   DATABASE_URL   (required for postgres)
+//!   --cookie-key <key>     COOKIE_KEY     (base64, at least 64 bytes; default: generated at startup)
+//!   --cookie-mode <mode>   COOKIE_MODE    (compat or strict; default: compat)
+//!   --pokeapi-url <url>    POKEAPI_URL    (default: https://pokeapi.co)
+//!   --xor-cube-max-numbers <n>    XOR_CUBE_MAX_NUMBERS   (integers in one day 1 request; default: 20)
+//!   --xor-cube-max-exponent <n>   XOR_CUBE_MAX_EXPONENT  (largest day 1 exponent; default: 64)
+use std::{
+    net::SocketAddr,
+    time::Duration,
+};
+
+use cch23_scd91::{
+    build_router,
+    days::{day1::XorCubeConfig, day19::WsSessions, day7::RecipeCookieConfig, day8::PokeApiConfig},
+    Storage,
+};
+use sqlx::PgPool;
+
+const DEFAULT_BIND_ADDR: &str = "0.0.0.0:8000";
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+enum StoreConfig {
+    Postgres { database_url: String },
+    Memory,
+}
+
+struct Args {
+    bind: SocketAddr,
+    store: StoreConfig,
+    recipe_cookies: RecipeCookieConfig,
+    pokeapi: PokeApiConfig,
+    xor_cube: XorCubeConfig,
+    generated_cookie_key: bool,
+}
+
+fn usage() -> String {
+    "Usage: standalone [--bind <addr>] [--store <postgres|memory>] [--database-url <url>] \
+        [--cookie-key <key>] [--cookie-mode <compat|strict>] [--pokeapi-url <url>] \
+        [--xor-cube-max-numbers <n>] [--xor-cube-max-exponent <n>]".into()
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut bind = std::env::var("BIND_ADDR").ok();
+    let mut store = std::env::var("ORDER_STORE").ok();
+    let mut database_url = std::env::var("DATABASE_URL").ok();
+    let mut cookie_key = std::env::var("COOKIE_KEY").ok();
+    let mut cookie_mode = std::env::var("COOKIE_MODE").ok();
+    let mut pokeapi_url = std::env::var("POKEAPI_URL").ok();
+    let mut xor_cube_max_numbers = std::env::var("XOR_CUBE_MAX_NUMBERS").ok();
+    let mut xor_cube_max_exponent = std::env::var("XOR_CUBE_MAX_EXPONENT").ok();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((f, v)) => (f.to_string(), Some(v.to_string())),
+            None => (arg, None),
+        };
+        let mut value = || inline_value.clone()
+            .or_else(|| args.next())
+            .ok_or_else(|| format!("Missing value for {}\n{}", flag, usage()));
+        match flag.as_str() {
+            "--bind" => { bind = Some(value()?); },
+            "--store" => { store = Some(value()?); },
+            "--database-url" => { database_url = Some(value()?); },
+            "--cookie-key" => { cookie_key = Some(value()?); },
+            "--cookie-mode" => { cookie_mode = Some(value()?); },
+            "--pokeapi-url" => { pokeapi_url = Some(value()?); },
+            "--xor-cube-max-numbers" => { xor_cube_max_numbers = Some(value()?); },
+            "--xor-cube-max-exponent" => { xor_cube_max_exponent = Some(value()?); },
+            "-h" | "--help" => { return Err(usage()); },
+            _ => { return Err(format!("Unknown argument: {}\n{}", flag, usage())); },
+        }
+    }
+
+    let bind = bind.as_deref()
+        .unwrap_or(DEFAULT_BIND_ADDR)
+        .parse::<SocketAddr>()
+        .map_err(|e| format!("Invalid bind address: {}", e))?;
+    let store = match (store.as_deref(), database_url) {
+        (Some("memory"), _) | (None, None) => StoreConfig::Memory,
+        (Some("postgres") | None, Some(database_url)) => StoreConfig::Postgres { database_url },
+        (Some("postgres"), None) =>
+            return Err(format!("A database URL is required for postgres.\n{}", usage())),
+        (Some(other), _) => return Err(format!("Unknown store: {}\n{}", other, usage())),
+    };
+
+    let recipe_cookies = RecipeCookieConfig::from_settings(cookie_key.as_deref(), cookie_mode.as_deref())
+        .map_err(|e| format!("{}\n{}", e, usage()))?;
+
+    let pokeapi = match pokeapi_url {
+        Some(base_url) => PokeApiConfig { base_url: base_url.trim_end_matches('/').into(), ..Default::default() },
+        None => PokeApiConfig::default(),
+    };
+
+    let xor_cube = XorCubeConfig::from_settings(xor_cube_max_numbers.as_deref(), xor_cube_max_exponent.as_deref())
+        .map_err(|e| format!("{}\n{}", e, usage()))?;
+
+    Ok(Args { bind, store, recipe_cookies, pokeapi, xor_cube, generated_cookie_key: cookie_key.is_none() })
+}
+
+// Resolves on SIGTERM or Ctrl-C.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut s) => { s.recv().await; },
+            Err(e) => {
+                tracing::error!("Unable to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            },
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Received shutdown signal.");
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    };
+
+    let storage = match &args.store {
+        StoreConfig::Postgres { database_url } => Storage::postgres(PgPool::connect(database_url).await?),
+        StoreConfig::Memory => {
+            tracing::info!("Using in-memory storage.");
+            Storage::in_memory()
+        },
+    };
+    if args.generated_cookie_key {
+        tracing::warn!("No cookie key given; issued recipe cookies will not verify after a restart.");
+    }
+    let ws_sessions = WsSessions::new();
+    let router = build_router(&storage, &ws_sessions, args.xor_cube, args.recipe_cookies, args.pokeapi);
+
+    tracing::info!("Listening on {}", args.bind);
+    axum::Server::bind(&args.bind)
+        .serve(router.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    // Upgraded WebSocket connections are not tracked by hyper, so close them separately.
+    tracing::info!("Draining {} WebSocket session(s).", ws_sessions.active());
+    if tokio::time::timeout(DRAIN_TIMEOUT, ws_sessions.drain()).await.is_err() {
+        tracing::warn!("Timed out draining WebSocket sessions.");
+    }
+
+    Ok(())
+}
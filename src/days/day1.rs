@@ -1,32 +1,172 @@
 use axum::{
-    extract::Path,
-    http::status::StatusCode,
-    routing::get,
+    extract::State,
+    routing::{get, post},
     Router
 };
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
 use std::{
-    ops::BitXor,
-    str::FromStr
+    str::FromStr,
+    sync::Arc
 };
 
-async fn xor_cube(Path(nums): Path<String>) -> Result<String, StatusCode> {
+use crate::{error::AppError, extract::{Json, Path, Query}};
+
+mod expr;
+
+pub struct XorCubeConfig {
+    /// Maximum number of integers in one request.
+    pub max_numbers: usize,
+    /// Largest exponent accepted, bounding the size of big-integer results.
+    pub max_exponent: u32,
+}
+
+impl Default for XorCubeConfig {
+    fn default() -> Self {
+        Self { max_numbers: 20, max_exponent: 64 }
+    }
+}
+
+impl XorCubeConfig {
+    /// Configuration from the limits as given to the server, each defaulting when absent.
+    pub fn from_settings(max_numbers: Option<&str>, max_exponent: Option<&str>) -> Result<Self, String> {
+        let defaults = Self::default();
+        let max_numbers = match max_numbers {
+            Some(s) => s.trim().parse().ok().filter(|&n| n > 0)
+                .ok_or_else(|| format!("Invalid XOR cube max numbers: {}", s))?,
+            None => defaults.max_numbers,
+        };
+        let max_exponent = match max_exponent {
+            Some(s) => s.trim().parse()
+                .map_err(|_| format!("Invalid XOR cube max exponent: {}", s))?,
+            None => defaults.max_exponent,
+        };
+        Ok(Self { max_numbers, max_exponent })
+    }
+}
+
+// How the numbers are combined before raising to the exponent.
+#[derive(Clone, Copy)]
+enum FoldOp {
+    Xor,
+    And,
+    Or,
+    Sum,
+    Product,
+}
+
+impl FromStr for FoldOp {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xor" => Ok(FoldOp::Xor),
+            "and" => Ok(FoldOp::And),
+            "or" => Ok(FoldOp::Or),
+            "sum" => Ok(FoldOp::Sum),
+            "product" => Ok(FoldOp::Product),
+            _ => Err(AppError::BadRequest(format!("Unknown operator: {}", s))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct XorCubeParams {
+    /// One of xor (the default), and, or, sum or product.
+    op: Option<String>,
+    /// Defaults to 3, hence the cube.
+    exp: Option<u32>,
+    /// Use arbitrary-precision integers, which never overflow.
+    #[serde(default)]
+    big: bool,
+}
+
+fn overflowed() -> AppError {
+    AppError::UnprocessableEntity("Result overflowed".into())
+}
+
+fn fold_i64(op: FoldOp, nums: &[i64], exp: u32) -> Result<String, AppError> {
+    let mut acc = nums[0];
+    for &n in &nums[1..] {
+        acc = match op {
+            FoldOp::Xor => acc ^ n,
+            FoldOp::And => acc & n,
+            FoldOp::Or => acc | n,
+            FoldOp::Sum => acc.checked_add(n).ok_or_else(overflowed)?,
+            FoldOp::Product => acc.checked_mul(n).ok_or_else(overflowed)?,
+        };
+    }
+    acc.checked_pow(exp)
+        .map(|x| x.to_string())
+        .ok_or_else(overflowed)
+}
+
+// Bitwise operators on `BigInt` behave as on two's complement, like `i64`.
+fn fold_big(op: FoldOp, nums: &[i64], exp: u32) -> String {
+    nums.iter()
+        .map(|&n| BigInt::from(n))
+        .reduce(|acc, n| match op {
+            FoldOp::Xor => acc ^ n,
+            FoldOp::And => acc & n,
+            FoldOp::Or => acc | n,
+            FoldOp::Sum => acc + n,
+            FoldOp::Product => acc * n,
+        })
+        .expect("At least one number should have been given.")
+        .pow(exp)
+        .to_string()
+}
+
+async fn xor_cube(
+    State(config): State<Arc<XorCubeConfig>>,
+    Path(nums): Path<String>,
+    Query(params): Query<XorCubeParams>,
+) -> Result<String, AppError> {
     let nums: Vec<Result<i64, std::num::ParseIntError>> = nums.split('/')
         .map(i64::from_str)
         .collect();
-    // Require 1-20 numbers
-    if nums.is_empty() || nums.len() > 20 || nums.iter().any(Result::is_err) {
-        return Result::Err(StatusCode::BAD_REQUEST)
+    // Require 1 to max_numbers numbers
+    if nums.is_empty() || nums.len() > config.max_numbers || nums.iter().any(Result::is_err) {
+        return Err(AppError::BadRequest(format!("Expected 1-{} integers", config.max_numbers)))
     }
+    let nums: Vec<i64> = nums.into_iter().map(Result::unwrap).collect();
 
-    let xor = nums.into_iter()
-        .map(Result::unwrap)
-        .fold(0i64, BitXor::bitxor);
-    let r = xor.checked_pow(3)
-        .map(|x| x.to_string())
-        .ok_or(StatusCode::UNPROCESSABLE_ENTITY);
-    r
+    let op = params.op.as_deref().map_or(Ok(FoldOp::Xor), FoldOp::from_str)?;
+    let exp = params.exp.unwrap_or(3);
+    if exp > config.max_exponent {
+        return Err(AppError::BadRequest(format!("Exponent must be at most {}", config.max_exponent)));
+    }
+
+    if params.big {
+        Ok(fold_big(op, &nums, exp))
+    } else {
+        fold_i64(op, &nums, exp)
+    }
+}
+
+#[derive(Deserialize)]
+struct EvalRequest {
+    expression: String,
+    /// Include the parse tree in the response.
+    #[serde(default)]
+    tree: bool,
+}
+
+#[derive(Serialize)]
+struct EvalResult {
+    result: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tree: Option<expr::Expr>,
+}
+
+async fn eval(Json(request): Json<EvalRequest>) -> Result<Json<EvalResult>, AppError> {
+    let tree = expr::parse(&request.expression)?;
+    let result = expr::evaluate(&tree)?;
+    Ok(Json(EvalResult { result, tree: request.tree.then_some(tree) }))
 }
 
-pub fn xor_cube_router() -> Router {
+pub fn xor_cube_router(config: XorCubeConfig) -> Router {
     Router::new().route("/*num", get(xor_cube))
+        .route("/eval", post(eval))
+        .with_state(Arc::new(config))
 }
\ No newline at end of file
@@ -0,0 +1,297 @@
+//! Integer expression language for `/1/eval`.
+//!
+//! Binary operators, from loosest to tightest: `|`, `^` (xor), `&`, `<<` `>>`,
+//! `+` `-`, `*` `/` `%`, then `**`, which is right-associative and binds tighter
+//! than unary `-` and `+` on its left. Functions are `abs`, `min`, `max`, `pow`
+//! and `cube`. All arithmetic is on `i64` and fails on overflow.
+use serde::Serialize;
+
+use crate::error::AppError;
+
+/// Parse tree, with the byte offset where each node starts.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Expr {
+    Number { offset: usize, value: i64 },
+    Unary { offset: usize, op: &'static str, operand: Box<Expr> },
+    Binary { offset: usize, op: &'static str, left: Box<Expr>, right: Box<Expr> },
+    Call { offset: usize, function: &'static str, args: Vec<Expr> },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Int(i64),
+    Ident(&'a str),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+    End,
+}
+
+// Longest operators first, so `**` is not read as two `*`.
+const OPERATORS: [&str; 11] = ["**", "<<", ">>", "^", "&", "|", "+", "-", "*", "/", "%"];
+
+// Functions with their minimum and maximum number of arguments.
+const FUNCTIONS: [(&str, usize, usize); 5] = [
+    ("abs", 1, 1),
+    ("min", 1, usize::MAX),
+    ("max", 1, usize::MAX),
+    ("pow", 2, 2),
+    ("cube", 1, 1),
+];
+
+// Bounds recursion on deeply nested input.
+const MAX_DEPTH: usize = 128;
+// Bounds the size of the tree, which is evaluated, serialized and dropped recursively. A flat
+// chain like `1+1+...+1` is never nested, but its tree is as deep as the chain is long.
+const MAX_NODES: usize = 1000;
+
+fn parse_error(offset: usize, message: impl Into<String>) -> AppError {
+    AppError::Parse { offset, message: message.into() }
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token<'_>)>, AppError> {
+    let mut tokens = Vec::new();
+    let mut rest = input.char_indices().peekable();
+    while let Some(&(start, c)) = rest.peek() {
+        if c.is_whitespace() {
+            rest.next();
+        } else if c.is_ascii_digit() || c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = rest.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + 1;
+                rest.next();
+            }
+            let word = &input[start..end];
+            let token = if c.is_ascii_digit() {
+                Token::Int(word.parse()
+                    .map_err(|_| parse_error(start, format!("Invalid integer: {}", word)))?)
+            } else {
+                Token::Ident(word)
+            };
+            tokens.push((start, token));
+        } else {
+            let token = match c {
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                ',' => Token::Comma,
+                _ => Token::Op(OPERATORS.into_iter()
+                    .find(|op| input[start..].starts_with(op))
+                    .ok_or_else(|| parse_error(start, format!("Unexpected character: {:?}", c)))?),
+            };
+            let len = match token {
+                Token::Op(op) => op.len(),
+                _ => 1,
+            };
+            for _ in 0..len {
+                rest.next();
+            }
+            tokens.push((start, token));
+        }
+    }
+    tokens.push((input.len(), Token::End));
+    Ok(tokens)
+}
+
+// Left and right binding power of each binary operator.
+fn binding_power(op: &str) -> (u8, u8) {
+    match op {
+        "|" => (1, 2),
+        "^" => (3, 4),
+        "&" => (5, 6),
+        "<<" | ">>" => (7, 8),
+        "+" | "-" => (9, 10),
+        "*" | "/" | "%" => (11, 12),
+        "**" => (16, 15),
+        _ => unreachable!("Every operator should have a binding power."),
+    }
+}
+
+const PREFIX_POWER: u8 = 13;
+
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    pos: usize,
+    depth: usize,
+    nodes: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> (usize, Token<'a>) {
+        self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> (usize, Token<'a>) {
+        let token = self.peek();
+        if token.1 != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), AppError> {
+        match self.next() {
+            (_, token) if token == expected => Ok(()),
+            (offset, _) => Err(parse_error(offset, format!("Expected {}", what))),
+        }
+    }
+
+    fn count_node(&mut self, offset: usize) -> Result<(), AppError> {
+        self.nodes += 1;
+        if self.nodes > MAX_NODES {
+            return Err(parse_error(offset, format!("Expression has more than {} terms", MAX_NODES)));
+        }
+        Ok(())
+    }
+
+    // Pratt parsing: keep taking operators that bind tighter than `min_power`.
+    fn expr(&mut self, min_power: u8) -> Result<Expr, AppError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(parse_error(self.peek().0, "Expression is nested too deeply"));
+        }
+
+        let mut left = self.prefix()?;
+        while let (offset, Token::Op(op)) = self.peek() {
+            let (left_power, right_power) = binding_power(op);
+            if left_power < min_power {
+                break;
+            }
+            self.count_node(offset)?;
+            self.next();
+            let right = self.expr(right_power)?;
+            left = Expr::Binary { offset, op, left: Box::new(left), right: Box::new(right) };
+        }
+
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    fn prefix(&mut self) -> Result<Expr, AppError> {
+        self.count_node(self.peek().0)?;
+        match self.next() {
+            (offset, Token::Int(value)) => Ok(Expr::Number { offset, value }),
+            (offset, Token::Op(op @ ("-" | "+"))) => {
+                let operand = self.expr(PREFIX_POWER)?;
+                Ok(Expr::Unary { offset, op, operand: Box::new(operand) })
+            },
+            (_, Token::LParen) => {
+                let inner = self.expr(0)?;
+                self.expect(Token::RParen, "')'")?;
+                Ok(inner)
+            },
+            (offset, Token::Ident(name)) => self.call(offset, name),
+            (offset, Token::End) => Err(parse_error(offset, "Unexpected end of expression")),
+            (offset, _) => Err(parse_error(offset, "Expected a number, function or '('")),
+        }
+    }
+
+    fn call(&mut self, offset: usize, name: &str) -> Result<Expr, AppError> {
+        let &(function, min_args, max_args) = FUNCTIONS.iter()
+            .find(|(f, _, _)| *f == name)
+            .ok_or_else(|| parse_error(offset, format!("Unknown function: {}", name)))?;
+        self.expect(Token::LParen, "'(' after function name")?;
+
+        let mut args = Vec::new();
+        if self.peek().1 != Token::RParen {
+            loop {
+                args.push(self.expr(0)?);
+                if self.peek().1 != Token::Comma {
+                    break;
+                }
+                self.next();
+            }
+        }
+        self.expect(Token::RParen, "',' or ')'")?;
+
+        if args.len() < min_args || args.len() > max_args {
+            return Err(parse_error(offset, format!("Wrong number of arguments to {}", function)));
+        }
+        Ok(Expr::Call { offset, function, args })
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, AppError> {
+    let mut parser = Parser { tokens: tokenize(input)?, pos: 0, depth: 0, nodes: 0 };
+    let expr = parser.expr(0)?;
+    match parser.peek() {
+        (_, Token::End) => Ok(expr),
+        (offset, Token::RParen) => Err(parse_error(offset, "Unmatched ')'")),
+        (offset, _) => Err(parse_error(offset, "Expected an operator")),
+    }
+}
+
+fn pow(base: i64, exp: i64) -> Result<i64, AppError> {
+    if exp < 0 {
+        return Err(AppError::UnprocessableEntity("Negative exponent".into()));
+    }
+    match base {
+        // Exponents too large for `checked_pow` are fine for these.
+        0 | 1 => Ok(if exp == 0 { 1 } else { base }),
+        -1 => Ok(if exp % 2 == 0 { 1 } else { -1 }),
+        _ => u32::try_from(exp).ok()
+            .and_then(|exp| base.checked_pow(exp))
+            .ok_or_else(super::overflowed),
+    }
+}
+
+fn shift(value: i64, op: &str, amount: i64) -> Result<i64, AppError> {
+    let amount = u32::try_from(amount).ok()
+        .filter(|&a| a < i64::BITS)
+        .ok_or_else(|| AppError::UnprocessableEntity(format!("Shift out of range: {}", amount)))?;
+    if op == ">>" {
+        return Ok(value >> amount);
+    }
+    // A left shift overflows if it loses any bits, including the sign.
+    let shifted = value << amount;
+    if shifted >> amount == value { Ok(shifted) } else { Err(super::overflowed()) }
+}
+
+fn divide(op: &str, left: i64, right: i64) -> Result<i64, AppError> {
+    if right == 0 {
+        return Err(AppError::UnprocessableEntity("Division by zero".into()));
+    }
+    // Truncating, as in Rust; only i64::MIN / -1 can overflow.
+    let result = if op == "/" { left.checked_div(right) } else { left.checked_rem(right) };
+    result.ok_or_else(super::overflowed)
+}
+
+pub fn evaluate(expr: &Expr) -> Result<i64, AppError> {
+    match expr {
+        Expr::Number { value, .. } => Ok(*value),
+        Expr::Unary { op, operand, .. } => {
+            let value = evaluate(operand)?;
+            if *op == "-" { value.checked_neg().ok_or_else(super::overflowed) } else { Ok(value) }
+        },
+        Expr::Binary { op, left, right, .. } => {
+            let (left, right) = (evaluate(left)?, evaluate(right)?);
+            match *op {
+                "|" => Ok(left | right),
+                "^" => Ok(left ^ right),
+                "&" => Ok(left & right),
+                "<<" | ">>" => shift(left, op, right),
+                "+" => left.checked_add(right).ok_or_else(super::overflowed),
+                "-" => left.checked_sub(right).ok_or_else(super::overflowed),
+                "*" => left.checked_mul(right).ok_or_else(super::overflowed),
+                "/" | "%" => divide(op, left, right),
+                "**" => pow(left, right),
+                _ => unreachable!("Every operator should be evaluated."),
+            }
+        },
+        Expr::Call { function, args, .. } => {
+            let args = args.iter().map(evaluate).collect::<Result<Vec<_>, _>>()?;
+            match *function {
+                "abs" => args[0].checked_abs().ok_or_else(super::overflowed),
+                "min" => Ok(args.into_iter().min().expect("min should have an argument.")),
+                "max" => Ok(args.into_iter().max().expect("max should have an argument.")),
+                "pow" => pow(args[0], args[1]),
+                "cube" => pow(args[0], 3),
+                _ => unreachable!("Every function should be evaluated."),
+            }
+        },
+    }
+}
\ No newline at end of file
@@ -1,61 +1,373 @@
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+
 use axum::{
-    extract::Multipart,
-    http::StatusCode,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
     routing::post,
     Router
 };
 use image::{
-    load_from_memory_with_format,
-    load_from_memory,
+    guess_format,
+    io::{Limits, Reader},
+    DynamicImage,
     ImageFormat
 };
-//use tokio::fs::read;
+use serde::{Deserialize, Serialize};
+use tokio::fs::read;
 use tower_http::services::ServeFile;
 
-async fn count_red_pixels(mut multipart: Multipart) -> Result<String, (StatusCode, String)> {
-    let mut result = String::new();
+use crate::{error::AppError, extract::{Json, Multipart, Query}};
+
+mod analysis;
+mod colors;
+mod regions;
+mod transform;
+use analysis::{ColorCounts, ColorStats, Quantizer};
+use colors::{Bucket, Classifier, ColorReport};
+use regions::{Connectivity, Mask};
+use transform::{Operation, OutputFormat};
+
+const DECORATION: &str = "assets/decoration.png";
+
+/// What to do with pixels that are not fully opaque.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AlphaPolicy {
+    /// Skip fully transparent pixels, and judge the rest by their color alone.
+    #[default]
+    Ignore,
+    /// Blend every pixel onto the background color first.
+    Composite,
+}
+
+#[derive(Deserialize)]
+struct AlphaParams {
+    #[serde(default)]
+    alpha: AlphaPolicy,
+    /// Hex RGB color to composite onto, e.g. `ffffff`.
+    background: Option<String>,
+}
+
+// Every format converts to 16-bit RGBA without losing precision, short of 32-bit float
+// channels, which are clamped. Calls `f` with the position and color of each pixel the alpha
+// policy keeps.
+fn for_each_pixel(
+    img: &DynamicImage,
+    params: &AlphaParams,
+    mut f: impl FnMut(u32, u32, [u16; 3]),
+) -> Result<(), AppError> {
+    const MAX: u32 = u16::MAX as u32;
+    let background = colors::parse_hex_color(params.background.as_deref().unwrap_or("ffffff"))?;
+    for (x, y, p) in img.to_rgba16().enumerate_pixels() {
+        let [r, g, b, a] = p.0;
+        match params.alpha {
+            AlphaPolicy::Ignore if a == 0 => {},
+            AlphaPolicy::Ignore => f(x, y, [r, g, b]),
+            AlphaPolicy::Composite => {
+                let a = a as u32;
+                let blend = |c: u16, bg: u16|
+                    ((c as u32 * a + bg as u32 * (MAX - a) + MAX / 2) / MAX) as u16;
+                f(x, y, [blend(r, background[0]), blend(g, background[1]), blend(b, background[2])]);
+            },
+        }
+    }
+    Ok(())
+}
+
+// Pixels in each bucket, and in none.
+fn classify(
+    img: &DynamicImage,
+    params: &AlphaParams,
+    classifier: &Classifier,
+) -> Result<(Vec<u64>, u64), AppError> {
+    let mut counts = vec![0; classifier.names().len()];
+    let mut unclassified = 0;
+    for_each_pixel(img, params, |_, _, rgb| match classifier.classify(rgb) {
+        Some(i) => counts[i] += 1,
+        None => unclassified += 1,
+    })?;
+    Ok((counts, unclassified))
+}
+
+// Limits for decoding uploads, so that oversized images are rejected from their headers, before
+// any pixels are allocated. The allocation limit leaves room for four 32-bit float channels.
+fn upload_limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(transform::MAX_DIMENSION);
+    limits.max_image_height = Some(transform::MAX_DIMENSION);
+    limits.max_alloc = Some(transform::MAX_PIXELS * 16);
+    limits
+}
+
+struct UploadedImage {
+    image: DynamicImage,
+    format: ImageFormat,
+}
+
+// Decodes an image by its format, within the upload limits.
+fn decode(data: &[u8], format: ImageFormat) -> Result<DynamicImage, AppError> {
+    let mut reader = Reader::with_format(Cursor::new(data), format);
+    reader.limits(upload_limits());
+    Ok(reader.decode()?)
+}
+
+/// Reads a multipart upload of at most `max_images` images, decoding each and passing it to
+/// `process` on the blocking pool before reading the next, so that only one is held at a time.
+/// Images are in the `image` fields, decoded by their content type if they have one, or else by
+/// their contents. Of the other fields, those in `text_fields` are returned by name, and the rest
+/// are skipped.
+async fn read_upload<T: Send + 'static>(
+    Multipart(mut multipart): Multipart,
+    max_images: usize,
+    text_fields: &[&str],
+    process: impl Fn(UploadedImage) -> Result<T, AppError> + Clone + Send + 'static,
+) -> Result<(Vec<T>, HashMap<String, String>), AppError> {
+    let mut results = Vec::new();
+    let mut fields = HashMap::new();
 
     while let Some(field) = multipart.next_field()
-        .await.map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to get multipart format: {}", e)))? {
-            if field.name() == Some("image") {
+        .await.map_err(|e| AppError::BadRequest(format!("Unable to get multipart format: {}", e)))? {
+            let Some(name) = field.name().map(|s| s.to_owned()) else { continue };
+            if name == "image" {
+                if results.len() == max_images {
+                    return Err(AppError::BadRequest(format!("Too many images, expected at most {}", max_images)));
+                }
                 let content_type = field.content_type().map(|s| s.to_owned()); // Clone type before consuming field.
 
                 let data = field.bytes().await
-                    .or(Err((StatusCode::BAD_REQUEST, "Unable to decode bytes.".into())))?;
-
-                let img = if let Some(mime_type) = content_type {
-                    let fmt = ImageFormat::from_mime_type(&mime_type)
-                        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unable to parse content type: {}", &mime_type)))?;
+                    .map_err(|e| AppError::BadRequest(format!("Unable to decode bytes: {}", e)))?;
 
-                    load_from_memory_with_format(&data, fmt)
+                let format = if let Some(mime_type) = content_type {
+                    ImageFormat::from_mime_type(&mime_type)
+                        .ok_or_else(|| AppError::BadRequest(format!("Unable to parse content type: {}", &mime_type)))?
                 } else {
-                    load_from_memory(&data)
-                }.map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to decode image: {}", e)))?;
-
-                let img_rgb = img.as_rgb8()
-                    .ok_or((StatusCode::BAD_REQUEST, "Cannot convert to RGB".into()))?;
-
-                let red_count = img_rgb.pixels()
-                    .filter(|p| {
-                        let [r, g, b] = p.0;
-                        match g.checked_add(b) {
-                            Some(s) => r > s,
-                            None => false
-                        }
-                    }).count();
-                // Add line in case of multi-output.
-                if !result.is_empty() {
-                    result.push_str("\r\n");
-                }
-
-                result.push_str(&red_count.to_string());
+                    guess_format(&data)?
+                };
+                let process = process.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    process(UploadedImage { image: decode(&data, format)?, format })
+                }).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+                results.push(result);
+            } else if text_fields.contains(&name.as_str()) {
+                let text = field.text().await
+                    .map_err(|e| AppError::BadRequest(format!("Unable to read field {}: {}", name, e)))?;
+                fields.insert(name, text);
             }
     }
 
-    Ok(result)
+    Ok((results, fields))
+}
+
+// The image of an upload with at most one, and its `text_fields`.
+async fn read_single_upload(
+    multipart: Multipart,
+    text_fields: &[&str],
+) -> Result<(Option<UploadedImage>, HashMap<String, String>), AppError> {
+    let (images, fields) = read_upload(multipart, 1, text_fields, Ok).await?;
+    Ok((images.into_iter().next(), fields))
+}
+
+fn expect_image(image: Option<UploadedImage>) -> Result<UploadedImage, AppError> {
+    image.ok_or_else(|| AppError::BadRequest("Expected exactly one image".into()))
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RedPixelsOutput {
+    /// The number of red pixels in each image, one per line.
+    #[default]
+    Count,
+    /// A PNG of the one image, with the red pixels as they are and the rest dimmed.
+    Mask,
+    /// JSON bounding box and connected regions of the red pixels in the one image.
+    Regions,
+}
+
+#[derive(Deserialize)]
+struct RedPixelsParams {
+    #[serde(default)]
+    output: RedPixelsOutput,
+    /// Whether pixels touching at a corner are in the same region, `8`, or not, `4`.
+    #[serde(default)]
+    connectivity: Connectivity,
+    /// Leave out regions of fewer pixels.
+    #[serde(default = "one")]
+    min_area: u64,
+}
+
+fn one() -> u64 {
+    1
+}
+
+// The red pixels of `image`.
+fn red_mask(image: &DynamicImage, alpha: &AlphaParams) -> Result<Mask, AppError> {
+    let classifier = Classifier::red();
+    let mut mask = Mask::new(image.width(), image.height());
+    for_each_pixel(image, alpha, |x, y, rgb| if classifier.classify(rgb).is_some() {
+        mask.set(x, y);
+    })?;
+    Ok(mask)
+}
+
+async fn count_red_pixels(
+    Query(alpha): Query<AlphaParams>,
+    Query(params): Query<RedPixelsParams>,
+    multipart: Multipart,
+) -> Result<Response, AppError> {
+    // Masking, labelling regions and encoding take a while for large images, so they run off the
+    // async executor.
+    match params.output {
+        RedPixelsOutput::Count => {},
+        RedPixelsOutput::Mask => {
+            let image = expect_image(read_single_upload(multipart, &[]).await?.0)?.image;
+            let png = tokio::task::spawn_blocking(move || {
+                let mask = red_mask(&image, &alpha)?;
+                let highlighted = DynamicImage::ImageRgba8(mask.highlight(&image));
+                transform::encode(&highlighted, OutputFormat::Png, None)
+            }).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+            return Ok(([(CONTENT_TYPE, OutputFormat::Png.mime_type())], png).into_response());
+        },
+        RedPixelsOutput::Regions => {
+            let image = expect_image(read_single_upload(multipart, &[]).await?.0)?.image;
+            let found = tokio::task::spawn_blocking(move || {
+                let mask = red_mask(&image, &alpha)?;
+                Ok::<_, AppError>(regions::regions(&mask, params.connectivity, params.min_area))
+            }).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+            return Ok(Json(found).into_response());
+        },
+    }
+
+    // Each image is counted as soon as it is decoded, so they are never all held at once.
+    let alpha = Arc::new(alpha);
+    let (counts, _) = read_upload(multipart, usize::MAX, &[], move |uploaded| {
+        let (counts, _) = classify(&uploaded.image, &alpha, &Classifier::red())?;
+        Ok(counts[0])
+    }).await?;
+
+    // One line per image.
+    let result = counts.iter().map(u64::to_string).collect::<Vec<_>>().join("\r\n");
+    Ok(result.into_response())
+}
+
+// Classify the pixels of the one `image` field by the JSON list of buckets in the `rules`
+// field, which defaults to just the red bucket.
+async fn count_colors(
+    Query(params): Query<AlphaParams>,
+    multipart: Multipart,
+) -> Result<Json<ColorReport>, AppError> {
+    let (image, fields) = read_single_upload(multipart, &["rules"]).await?;
+    let image = expect_image(image)?.image;
+    let classifier = match fields.get("rules") {
+        Some(rules) => {
+            let buckets: Vec<Bucket> = serde_json::from_str(rules)
+                .map_err(|e| AppError::BadRequest(format!("Invalid rules: {}", e)))?;
+            Classifier::new(buckets)?
+        },
+        None => Classifier::red(),
+    };
+
+    // Classifying every pixel takes a while for large images, so it runs off the async executor.
+    let report = tokio::task::spawn_blocking(move || {
+        let (counts, unclassified) = classify(&image, &params, &classifier)?;
+        Ok::<_, AppError>(ColorReport::new(&classifier, &counts, unclassified))
+    }).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize)]
+struct AnalyzeParams {
+    /// How many dominant colors to find.
+    colors: Option<usize>,
+    #[serde(default)]
+    quantizer: Quantizer,
+}
+
+#[derive(Serialize)]
+struct Analysis {
+    width: u32,
+    height: u32,
+    /// Such as `png` or `jpeg`.
+    format: String,
+    /// As decoded, such as `rgba8` or `l16`.
+    color_type: String,
+    #[serde(flatten)]
+    stats: ColorStats,
+}
+
+// Describe the one `image` field: its size, format and colors.
+async fn analyze(
+    Query(alpha): Query<AlphaParams>,
+    Query(params): Query<AnalyzeParams>,
+    multipart: Multipart,
+) -> Result<Json<Analysis>, AppError> {
+    let colors = params.colors.unwrap_or(analysis::DEFAULT_COLORS);
+    if !(1..=analysis::MAX_COLORS).contains(&colors) {
+        return Err(AppError::BadRequest(format!("Expected 1-{} colors", analysis::MAX_COLORS)));
+    }
+    let uploaded = expect_image(read_single_upload(multipart, &[]).await?.0)?;
+
+    // Counting colors and clustering them take a while for large images, so they run off the
+    // async executor.
+    let analysis = tokio::task::spawn_blocking(move || {
+        let mut counts = ColorCounts::default();
+        for_each_pixel(&uploaded.image, &alpha, |_, _, rgb| counts.add(rgb))?;
+        Ok::<_, AppError>(Analysis {
+            width: uploaded.image.width(),
+            height: uploaded.image.height(),
+            format: format!("{:?}", uploaded.format).to_lowercase(),
+            color_type: format!("{:?}", uploaded.image.color()).to_lowercase(),
+            stats: counts.stats(colors, params.quantizer),
+        })
+    }).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+    Ok(Json(analysis))
+}
+
+#[derive(Deserialize)]
+struct TransformParams {
+    #[serde(default)]
+    format: OutputFormat,
+    /// From 1 to 100, for JPEG and lossy WebP.
+    quality: Option<u8>,
+}
+
+// Apply the JSON list of operations in the `operations` field to the one `image` field, or to
+// the decoration without one, and return the result in the requested format.
+async fn transform(
+    Query(params): Query<TransformParams>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(quality) = params.quality {
+        if !params.format.is_lossy() {
+            return Err(AppError::BadRequest("Quality only applies to jpeg and webp".into()));
+        }
+        if !(1..=100).contains(&quality) {
+            return Err(AppError::BadRequest("Expected a quality of 1-100".into()));
+        }
+    }
+    let (image, fields) = read_single_upload(multipart, &["operations"]).await?;
+    let operations: Vec<Operation> = match fields.get("operations") {
+        Some(operations) => serde_json::from_str(operations)
+            .map_err(|e| AppError::BadRequest(format!("Invalid operations: {}", e)))?,
+        None => Vec::new(),
+    };
+    // Without an upload, the decoration is read here and decoded with the other pixel work.
+    let decoration = if image.is_none() { read(DECORATION).await? } else { Vec::new() };
+
+    let encoded = tokio::task::spawn_blocking(move || {
+        let image = match image {
+            Some(uploaded) => uploaded.image,
+            None => decode(&decoration, ImageFormat::Png)?,
+        };
+        let image = transform::apply(image, &operations)?;
+        transform::encode(&image, params.format, params.quality)
+    }).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+    Ok(([(CONTENT_TYPE, params.format.mime_type())], encoded))
 }
 
 pub fn ornament_router() -> Router {
-    Router::new().nest_service("/assets/decoration.png", ServeFile::new("assets/decoration.png"))
+    Router::new().nest_service("/assets/decoration.png", ServeFile::new(DECORATION))
         .route("/red_pixels", post(count_red_pixels))
+        .route("/colors", post(count_colors))
+        .route("/analyze", post(analyze))
+        .route("/transform", post(transform))
 }
\ No newline at end of file
@@ -0,0 +1,195 @@
+//! Histograms, average colors and dominant colors of an image.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_COLORS: usize = 5;
+pub const MAX_COLORS: usize = 32;
+const KMEANS_ITERATIONS: usize = 20;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantizer {
+    /// Split the color space at medians until there are enough boxes.
+    #[default]
+    MedianCut,
+    /// Refine the median-cut colors by k-means, which fits clusters more closely.
+    Kmeans,
+}
+
+#[derive(Serialize)]
+pub struct Histograms {
+    pub r: Vec<u64>,
+    pub g: Vec<u64>,
+    pub b: Vec<u64>,
+}
+
+#[derive(Serialize)]
+pub struct Color {
+    pub hex: String,
+    pub rgb: [u8; 3],
+}
+
+impl From<[u8; 3]> for Color {
+    fn from(rgb: [u8; 3]) -> Self {
+        Color { hex: format!("{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]), rgb }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DominantColor {
+    #[serde(flatten)]
+    pub color: Color,
+    pub count: u64,
+    /// Of every pixel counted, from 0 to 100.
+    pub percentage: f64,
+}
+
+#[derive(Serialize)]
+pub struct ColorStats {
+    /// Pixels counted, leaving out those skipped as transparent.
+    pub pixels: u64,
+    /// Counts of each 8-bit value, per channel.
+    pub histogram: Histograms,
+    /// Per channel, on the 0-255 scale. Absent if no pixel was counted.
+    pub mean: Option<[f64; 3]>,
+    pub median: Option<Color>,
+    /// Most common first.
+    pub dominant: Vec<DominantColor>,
+}
+
+/// Pixels seen so far, by distinct 8-bit color.
+#[derive(Default)]
+pub struct ColorCounts {
+    counts: HashMap<[u8; 3], u64>,
+}
+
+impl ColorCounts {
+    /// Count a 16-bit pixel, rounded to 8 bits.
+    pub fn add(&mut self, rgb: [u16; 3]) {
+        let rgb = rgb.map(|c| ((c as u32 + 128) / 257) as u8);
+        *self.counts.entry(rgb).or_default() += 1;
+    }
+
+    pub fn stats(&self, colors: usize, quantizer: Quantizer) -> ColorStats {
+        let mut histogram = Histograms { r: vec![0; 256], g: vec![0; 256], b: vec![0; 256] };
+        let mut sums = [0.0; 3];
+        for (&[r, g, b], &count) in &self.counts {
+            histogram.r[r as usize] += count;
+            histogram.g[g as usize] += count;
+            histogram.b[b as usize] += count;
+            for (sum, c) in sums.iter_mut().zip([r, g, b]) {
+                *sum += c as f64 * count as f64;
+            }
+        }
+        let pixels: u64 = self.counts.values().sum();
+        if pixels == 0 {
+            return ColorStats { pixels, histogram, mean: None, median: None, dominant: Vec::new() };
+        }
+
+        // The lower median, so it is always a value that occurs.
+        let median = |bins: &[u64]| {
+            let mut seen = 0;
+            bins.iter().position(|&n| { seen += n; seen * 2 >= pixels }).unwrap_or(0) as u8
+        };
+        let median = Color::from([median(&histogram.r), median(&histogram.g), median(&histogram.b)]);
+
+        let weighted: Vec<([u8; 3], u64)> = self.counts.iter().map(|(&c, &n)| (c, n)).collect();
+        let mut clusters = median_cut(weighted.clone(), colors);
+        if let Quantizer::Kmeans = quantizer {
+            clusters = kmeans(&weighted, clusters);
+        }
+        clusters.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let dominant = clusters.into_iter()
+            .map(|(rgb, count)| DominantColor {
+                color: Color::from(rgb),
+                count,
+                percentage: count as f64 * 100.0 / pixels as f64,
+            })
+            .collect();
+
+        ColorStats {
+            pixels,
+            histogram,
+            mean: Some(sums.map(|sum| sum / pixels as f64)),
+            median: Some(median),
+            dominant,
+        }
+    }
+}
+
+fn weighted_mean(colors: &[([u8; 3], u64)]) -> ([u8; 3], u64) {
+    let total: u64 = colors.iter().map(|(_, n)| n).sum();
+    let mut sums = [0u64; 3];
+    for (color, n) in colors {
+        for (sum, &c) in sums.iter_mut().zip(color) {
+            *sum += c as u64 * n;
+        }
+    }
+    (sums.map(|sum| ((sum + total / 2) / total) as u8), total)
+}
+
+// Channel with the widest range in a box, and that range.
+fn widest_channel(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|i| {
+            let values = colors.iter().map(|(c, _)| c[i]);
+            (i, values.clone().max().unwrap_or(0) - values.min().unwrap_or(0))
+        })
+        .max_by_key(|&(i, range)| (range, std::cmp::Reverse(i)))
+        .expect("There should be three channels.")
+}
+
+// Up to `k` colors, each the mean of a box, with its pixel count.
+fn median_cut(colors: Vec<([u8; 3], u64)>, k: usize) -> Vec<([u8; 3], u64)> {
+    let mut boxes = vec![colors];
+    while boxes.len() < k {
+        // Split the box spanning the widest range, at its weighted median.
+        let Some((index, (channel, _))) = boxes.iter()
+            .map(|b| widest_channel(b))
+            .enumerate()
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|&(i, (_, range))| (range, std::cmp::Reverse(i)))
+        else {
+            break;
+        };
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|(c, _)| (c[channel], *c));
+        let total: u64 = colors.iter().map(|(_, n)| n).sum();
+        let mut seen = 0;
+        let split = colors.iter()
+            .position(|(_, n)| { seen += n; seen * 2 >= total })
+            .map_or(1, |i| i + 1)
+            .clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|b| weighted_mean(b)).collect()
+}
+
+fn distance_squared(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&x, y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+// Lloyd's algorithm from the given centers, dropping any that lose all their colors.
+fn kmeans(colors: &[([u8; 3], u64)], mut centers: Vec<([u8; 3], u64)>) -> Vec<([u8; 3], u64)> {
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut members: Vec<Vec<([u8; 3], u64)>> = vec![Vec::new(); centers.len()];
+        for &(color, n) in colors {
+            let nearest = (0..centers.len())
+                .min_by_key(|&i| distance_squared(color, centers[i].0))
+                .expect("There should be at least one center.");
+            members[nearest].push((color, n));
+        }
+        let next: Vec<([u8; 3], u64)> = members.iter()
+            .filter(|m| !m.is_empty())
+            .map(|m| weighted_mean(m))
+            .collect();
+        if next == centers {
+            break;
+        }
+        centers = next;
+    }
+    centers
+}
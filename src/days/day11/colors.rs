@@ -0,0 +1,235 @@
+//! Classification of pixels into named color buckets, by configurable rules.
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+pub const MAX_BUCKETS: usize = 64;
+const MAX_PALETTE_COLORS: usize = 256;
+// 16-bit channels are this many times their 8-bit equivalents.
+const CHANNEL_SCALE: f64 = 257.0;
+
+/// A hex RGB color, such as `ffd700` or `#ffd700`, scaled to 16 bits per channel.
+pub fn parse_hex_color(hex: &str) -> Result<[u16; 3], AppError> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    let channel = |i: usize| digits.get(i..i + 2)
+        .filter(|_| digits.len() == 6)
+        .and_then(|c| u8::from_str_radix(c, 16).ok())
+        .map(|c| c as u16 * 257)
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid color: {}", hex)));
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// `r * R + g * G + b * B > above`, with channels and `above` on the 0-255 scale.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RgbCondition {
+    #[serde(default)]
+    pub r: f64,
+    #[serde(default)]
+    pub g: f64,
+    #[serde(default)]
+    pub b: f64,
+    #[serde(default)]
+    pub above: f64,
+}
+
+impl RgbCondition {
+    // Evaluated on 16-bit channels, so integer weights compare exactly.
+    fn holds(&self, [r, g, b]: [u16; 3]) -> bool {
+        self.r * r as f64 + self.g * g as f64 + self.b * b as f64 > self.above * CHANNEL_SCALE
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Rule {
+    /// Every condition holds.
+    Rgb { all: Vec<RgbCondition> },
+    /// Hue in degrees, from `hue[0]` to `hue[1]`, wrapping past 360; saturation and value
+    /// from 0 to 1.
+    Hsv {
+        hue: [f64; 2],
+        #[serde(default)]
+        min_saturation: f64,
+        #[serde(default = "one")]
+        max_saturation: f64,
+        #[serde(default)]
+        min_value: f64,
+        #[serde(default = "one")]
+        max_value: f64,
+    },
+    /// Nearer to one of `colors` than to any other palette bucket's colors, and within
+    /// `max_distance` of it on the 0-255 scale, if given.
+    Palette {
+        colors: Vec<String>,
+        max_distance: Option<f64>,
+    },
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Bucket {
+    pub name: String,
+    pub rule: Rule,
+}
+
+// A bucket's rule, ready to test pixels.
+enum Matcher {
+    Rgb(Vec<RgbCondition>),
+    Hsv { hue: [f64; 2], saturation: [f64; 2], value: [f64; 2] },
+    Palette { max_distance: Option<f64> },
+}
+
+/// Buckets ready to classify pixels. Each pixel goes to the first bucket whose rule it
+/// matches, if any.
+pub struct Classifier {
+    names: Vec<String>,
+    matchers: Vec<Matcher>,
+    // Every palette color on the 0-255 scale, with the index of its bucket.
+    palette: Vec<([f64; 3], usize)>,
+}
+
+fn hsv([r, g, b]: [u16; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = [r, g, b].map(|c| c as f64 / u16::MAX as f64);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f64>().sqrt()
+}
+
+impl Classifier {
+    pub fn new(buckets: Vec<Bucket>) -> Result<Self, AppError> {
+        if buckets.is_empty() || buckets.len() > MAX_BUCKETS {
+            return Err(AppError::BadRequest(format!("Expected 1-{} buckets", MAX_BUCKETS)));
+        }
+        let mut seen = HashSet::new();
+        if let Some(bucket) = buckets.iter().find(|b| !seen.insert(b.name.as_str())) {
+            return Err(AppError::BadRequest(format!("Duplicate bucket: {}", bucket.name)));
+        }
+
+        let mut palette = Vec::new();
+        let mut matchers = Vec::with_capacity(buckets.len());
+        for (i, bucket) in buckets.iter().enumerate() {
+            matchers.push(match &bucket.rule {
+                Rule::Rgb { all } => Matcher::Rgb(all.clone()),
+                &Rule::Hsv { hue, min_saturation, max_saturation, min_value, max_value } => {
+                    if hue.iter().any(|h| !(0.0..=360.0).contains(h)) {
+                        return Err(AppError::BadRequest(format!("Hue of {} must be 0-360 degrees", bucket.name)));
+                    }
+                    Matcher::Hsv {
+                        hue,
+                        saturation: [min_saturation, max_saturation],
+                        value: [min_value, max_value],
+                    }
+                },
+                Rule::Palette { colors, max_distance } => {
+                    if colors.is_empty() || colors.len() > MAX_PALETTE_COLORS {
+                        return Err(AppError::BadRequest(format!(
+                            "Palette of {} must have 1-{} colors", bucket.name, MAX_PALETTE_COLORS
+                        )));
+                    }
+                    for color in colors {
+                        let color = parse_hex_color(color)?.map(|c| c as f64 / CHANNEL_SCALE);
+                        palette.push((color, i));
+                    }
+                    Matcher::Palette { max_distance: *max_distance }
+                },
+            });
+        }
+        Ok(Self { names: buckets.into_iter().map(|b| b.name).collect(), matchers, palette })
+    }
+
+    /// Classifies pixels as the one bucket `r > g + b`, as `/11/red_pixels` always has.
+    pub fn red() -> Self {
+        let red = Bucket {
+            name: "red".into(),
+            rule: Rule::Rgb { all: vec![RgbCondition { r: 1.0, g: -1.0, b: -1.0, above: 0.0 }] },
+        };
+        Self::new(vec![red]).expect("The red rule should be valid.")
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Index of the bucket a 16-bit RGB pixel belongs to.
+    pub fn classify(&self, rgb: [u16; 3]) -> Option<usize> {
+        let mut hsv_cache = None;
+        let mut nearest_cache = None;
+        self.matchers.iter().enumerate().find(|&(i, matcher)| match matcher {
+            Matcher::Rgb(conditions) => conditions.iter().all(|c| c.holds(rgb)),
+            Matcher::Hsv { hue, saturation, value } => {
+                let (h, s, v) = *hsv_cache.get_or_insert_with(|| hsv(rgb));
+                let in_hue = if hue[0] <= hue[1] {
+                    (hue[0]..=hue[1]).contains(&h)
+                } else {
+                    h >= hue[0] || h <= hue[1]
+                };
+                in_hue && (saturation[0]..=saturation[1]).contains(&s) && (value[0]..=value[1]).contains(&v)
+            },
+            Matcher::Palette { max_distance } => {
+                let (bucket, distance) = *nearest_cache.get_or_insert_with(|| self.nearest(rgb));
+                bucket == i && max_distance.is_none_or(|max| distance <= max)
+            },
+        }).map(|(i, _)| i)
+    }
+
+    // The palette bucket with the nearest color, and the distance to it.
+    fn nearest(&self, rgb: [u16; 3]) -> (usize, f64) {
+        let rgb = rgb.map(|c| c as f64 / CHANNEL_SCALE);
+        self.palette.iter()
+            .map(|&(color, bucket)| (bucket, distance(rgb, color)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("A palette bucket should have colors.")
+    }
+}
+
+#[derive(Serialize)]
+pub struct BucketCount {
+    pub name: String,
+    pub count: u64,
+    /// Of every pixel counted, from 0 to 100.
+    pub percentage: f64,
+}
+
+#[derive(Serialize)]
+pub struct ColorReport {
+    /// Pixels counted, leaving out those skipped as transparent.
+    pub total: u64,
+    pub buckets: Vec<BucketCount>,
+    /// Pixels in no bucket.
+    pub unclassified: BucketCount,
+}
+
+impl ColorReport {
+    pub fn new(classifier: &Classifier, counts: &[u64], unclassified: u64) -> Self {
+        let total = counts.iter().sum::<u64>() + unclassified;
+        let bucket = |name: &str, count: u64| BucketCount {
+            name: name.to_string(),
+            count,
+            percentage: if total == 0 { 0.0 } else { count as f64 * 100.0 / total as f64 },
+        };
+        Self {
+            total,
+            buckets: classifier.names().iter().zip(counts).map(|(name, &count)| bucket(name, count)).collect(),
+            unclassified: bucket("unclassified", unclassified),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Where the matching pixels of an image are: a highlighted mask, and their connected regions.
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use image::{DynamicImage, Luma, Pixel, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// At most this many regions are listed, largest first.
+pub const MAX_REGIONS: usize = 1000;
+// Brightness of the pixels that do not match, in the mask.
+const DIM: f32 = 0.25;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum Connectivity {
+    /// Pixels touch along an edge.
+    #[serde(rename = "4")]
+    Four,
+    /// Pixels touch along an edge or at a corner.
+    #[default]
+    #[serde(rename = "8")]
+    Eight,
+}
+
+/// Which pixels of an image match.
+pub struct Mask {
+    width: u32,
+    height: u32,
+    bits: Vec<bool>,
+}
+
+impl Mask {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, bits: vec![false; width as usize * height as usize] }
+    }
+
+    pub fn set(&mut self, x: u32, y: u32) {
+        let i = self.index(x, y);
+        self.bits[i] = true;
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        self.bits[self.index(x, y)]
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// `img` with the matching pixels as they are, and the rest dimmed to gray.
+    pub fn highlight(&self, img: &DynamicImage) -> RgbaImage {
+        let mut out = img.to_rgba8();
+        for (x, y, p) in out.enumerate_pixels_mut() {
+            if !self.get(x, y) {
+                let Luma([gray]) = p.to_luma();
+                let gray = (gray as f32 * DIM).round() as u8;
+                *p = Rgba([gray, gray, gray, p[3]]);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct BoundingBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Mean pixel coordinates, where pixel `(x, y)` is the unit square from `x` to `x + 1`.
+#[derive(Serialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Serialize)]
+pub struct Region {
+    pub area: u64,
+    pub bounding_box: BoundingBox,
+    pub centroid: Point,
+}
+
+#[derive(Serialize)]
+pub struct RegionReport {
+    /// Matching pixels in all regions.
+    pub pixels: u64,
+    /// Around every matching pixel, if any match.
+    pub bounding_box: Option<BoundingBox>,
+    /// Regions of at least the minimum area, including those past `MAX_REGIONS`.
+    pub region_count: usize,
+    /// Largest first, then from the top left.
+    pub regions: Vec<Region>,
+}
+
+// Running extent and coordinate sums of a set of pixels.
+#[derive(Clone)]
+struct Extent {
+    min: (u32, u32),
+    max: (u32, u32),
+    area: u64,
+    sum: (u64, u64),
+}
+
+impl Extent {
+    fn new(x: u32, y: u32) -> Self {
+        Self { min: (x, y), max: (x, y), area: 0, sum: (0, 0) }
+    }
+
+    fn add(&mut self, x: u32, y: u32) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+        self.area += 1;
+        self.sum = (self.sum.0 + x as u64, self.sum.1 + y as u64);
+    }
+
+    fn merge(&mut self, other: &Extent) {
+        self.min = (self.min.0.min(other.min.0), self.min.1.min(other.min.1));
+        self.max = (self.max.0.max(other.max.0), self.max.1.max(other.max.1));
+        self.area += other.area;
+        self.sum = (self.sum.0 + other.sum.0, self.sum.1 + other.sum.1);
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        BoundingBox {
+            x: self.min.0,
+            y: self.min.1,
+            width: self.max.0 - self.min.0 + 1,
+            height: self.max.1 - self.min.1 + 1,
+        }
+    }
+
+    fn region(&self) -> Region {
+        let area = self.area as f64;
+        Region {
+            area: self.area,
+            bounding_box: self.bounding_box(),
+            centroid: Point { x: self.sum.0 as f64 / area + 0.5, y: self.sum.1 as f64 / area + 0.5 },
+        }
+    }
+}
+
+// A region kept for listing. Those listed later, being smaller or found later in the scan,
+// compare greater, so the heap of kept regions has the first to drop on top.
+struct Found {
+    order: usize,
+    extent: Extent,
+}
+
+impl Ord for Found {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.extent.area.cmp(&self.extent.area).then(self.order.cmp(&other.order))
+    }
+}
+
+impl PartialOrd for Found {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Found {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Found {}
+
+/// Label the connected regions of `mask`, leaving out those smaller than `min_area`.
+pub fn regions(mask: &Mask, connectivity: Connectivity, min_area: u64) -> RegionReport {
+    let neighbours: &[(i64, i64)] = match connectivity {
+        Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+        Connectivity::Eight => &[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)],
+    };
+    let mut visited = vec![false; mask.bits.len()];
+    let mut stack = Vec::new();
+    let mut total: Option<Extent> = None;
+    // Only the regions that will be listed are kept, however many there are.
+    let mut found = BinaryHeap::with_capacity(MAX_REGIONS + 1);
+    let mut region_count = 0;
+
+    // Scanning in row order finds each region first at its top left.
+    for y in 0..mask.height {
+        for x in 0..mask.width {
+            if !mask.get(x, y) || visited[mask.index(x, y)] {
+                continue;
+            }
+            let mut extent = Extent::new(x, y);
+            visited[mask.index(x, y)] = true;
+            stack.push((x, y));
+            while let Some((px, py)) = stack.pop() {
+                extent.add(px, py);
+                for (dx, dy) in neighbours {
+                    let (nx, ny) = (px as i64 + dx, py as i64 + dy);
+                    if nx < 0 || ny < 0 || nx >= mask.width as i64 || ny >= mask.height as i64 {
+                        continue;
+                    }
+                    let (nx, ny) = (nx as u32, ny as u32);
+                    let i = mask.index(nx, ny);
+                    if mask.get(nx, ny) && !visited[i] {
+                        visited[i] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+
+            match &mut total {
+                Some(total) => total.merge(&extent),
+                None => total = Some(extent.clone()),
+            }
+            if extent.area >= min_area {
+                found.push(Found { order: region_count, extent });
+                region_count += 1;
+                if found.len() > MAX_REGIONS {
+                    found.pop();
+                }
+            }
+        }
+    }
+
+    RegionReport {
+        pixels: total.as_ref().map_or(0, |t| t.area),
+        bounding_box: total.as_ref().map(Extent::bounding_box),
+        region_count,
+        regions: found.into_sorted_vec().iter().map(|found| found.extent.region()).collect(),
+    }
+}
\ No newline at end of file
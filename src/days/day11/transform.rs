@@ -0,0 +1,220 @@
+//! Image transformations, applied in order, and encoding of the result.
+use std::io::Cursor;
+
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        webp::{WebPEncoder, WebPQuality},
+    },
+    imageops::FilterType,
+    ColorType,
+    DynamicImage,
+    ImageOutputFormat,
+};
+use serde::Deserialize;
+
+use super::colors::{parse_hex_color, Classifier};
+use crate::error::AppError;
+
+/// Largest width or height of any image, as uploaded or after an operation.
+pub const MAX_DIMENSION: u32 = 8192;
+/// Largest number of pixels in any image, as uploaded or after an operation.
+pub const MAX_PIXELS: u64 = 16 * 1024 * 1024;
+pub const MAX_OPERATIONS: usize = 32;
+const MAX_BLUR_SIGMA: f32 = 50.0;
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    /// Keep the aspect ratio, as large as fits within the size.
+    #[default]
+    Fit,
+    /// Stretch to exactly the size.
+    Exact,
+    /// Keep the aspect ratio, cover the size, and crop the excess.
+    Fill,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Resize {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        mode: ResizeMode,
+    },
+    /// The rectangle must lie within the image.
+    Crop { x: u32, y: u32, width: u32, height: u32 },
+    /// Clockwise, by a multiple of 90 degrees.
+    Rotate { degrees: i32 },
+    Flip { direction: FlipDirection },
+    Grayscale,
+    /// Gaussian blur with a standard deviation of `sigma` pixels.
+    Blur { sigma: f32 },
+    /// Rotate every hue by `degrees`.
+    HueShift { degrees: i32 },
+    /// Paint the pixels that `/red_pixels` counts in a hex color, keeping their alpha.
+    #[serde(alias = "recolour_red")]
+    RecolorRed { color: String },
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Webp,
+    Gif,
+}
+
+impl OutputFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Gif => "image/gif",
+        }
+    }
+
+    /// Whether the format takes a quality, from 1 to 100.
+    pub fn is_lossy(self) -> bool {
+        matches!(self, OutputFormat::Jpeg | OutputFormat::Webp)
+    }
+}
+
+/// Reject images that are empty or over the limits.
+pub fn check_size(width: u32, height: u32) -> Result<(), AppError> {
+    if width == 0 || height == 0 {
+        return Err(AppError::BadRequest("Images must be at least 1x1 pixels".into()));
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION || width as u64 * height as u64 > MAX_PIXELS {
+        return Err(AppError::BadRequest(format!(
+            "Images are limited to {}x{} pixels, and {} pixels in total, but this one is {}x{}",
+            MAX_DIMENSION, MAX_DIMENSION, MAX_PIXELS, width, height,
+        )));
+    }
+    Ok(())
+}
+
+/// Apply each operation in turn, checking the size after each.
+pub fn apply(mut img: DynamicImage, operations: &[Operation]) -> Result<DynamicImage, AppError> {
+    if operations.len() > MAX_OPERATIONS {
+        return Err(AppError::BadRequest(format!("Expected at most {} operations", MAX_OPERATIONS)));
+    }
+    check_size(img.width(), img.height())?;
+    for operation in operations {
+        img = apply_one(img, operation)?;
+        check_size(img.width(), img.height())?;
+    }
+    Ok(img)
+}
+
+fn apply_one(img: DynamicImage, operation: &Operation) -> Result<DynamicImage, AppError> {
+    Ok(match *operation {
+        Operation::Resize { width, height, mode } => {
+            // Check the target first, so an oversized one is never allocated.
+            check_size(width, height)?;
+            match mode {
+                ResizeMode::Fit => img.resize(width, height, FilterType::CatmullRom),
+                ResizeMode::Exact => img.resize_exact(width, height, FilterType::CatmullRom),
+                ResizeMode::Fill => img.resize_to_fill(width, height, FilterType::CatmullRom),
+            }
+        },
+        Operation::Crop { x, y, width, height } => {
+            if x as u64 + width as u64 > img.width() as u64 || y as u64 + height as u64 > img.height() as u64 {
+                return Err(AppError::BadRequest(format!(
+                    "Crop of {}x{} at ({}, {}) is outside the {}x{} image",
+                    width, height, x, y, img.width(), img.height(),
+                )));
+            }
+            check_size(width, height)?;
+            img.crop_imm(x, y, width, height)
+        },
+        Operation::Rotate { degrees } => match degrees.rem_euclid(360) {
+            0 => img,
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => return Err(AppError::BadRequest(format!(
+                "Can only rotate by multiples of 90 degrees, not {}", degrees
+            ))),
+        },
+        Operation::Flip { direction: FlipDirection::Horizontal } => img.fliph(),
+        Operation::Flip { direction: FlipDirection::Vertical } => img.flipv(),
+        Operation::Grayscale => img.grayscale(),
+        Operation::Blur { sigma } => {
+            if !(sigma > 0.0 && sigma <= MAX_BLUR_SIGMA) {
+                return Err(AppError::BadRequest(format!(
+                    "Blur sigma must be above 0 and at most {}", MAX_BLUR_SIGMA
+                )));
+            }
+            img.blur(sigma)
+        },
+        Operation::HueShift { degrees } => hue_shift(img, degrees),
+        Operation::RecolorRed { ref color } => recolor_red(&img, parse_hex_color(color)?),
+    })
+}
+
+// `huerotate` clamps every channel to 255, so only 8-bit images can go through it as they are.
+fn hue_shift(img: DynamicImage, degrees: i32) -> DynamicImage {
+    match img {
+        // Gray has no hue to shift.
+        _ if !img.color().has_color() => img,
+        DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => img.huerotate(degrees),
+        _ => DynamicImage::ImageRgba8(img.to_rgba8()).huerotate(degrees),
+    }
+}
+
+// Compared at 16 bits like `/red_pixels`, skipping fully transparent pixels as it does by default.
+fn recolor_red(img: &DynamicImage, color: [u16; 3]) -> DynamicImage {
+    let classifier = Classifier::red();
+    let mut pixels = img.to_rgba16();
+    for p in pixels.pixels_mut() {
+        let [r, g, b, a] = p.0;
+        if a > 0 && classifier.classify([r, g, b]).is_some() {
+            p.0 = [color[0], color[1], color[2], a];
+        }
+    }
+    let recolored = DynamicImage::ImageRgba16(pixels);
+    // Keep 8-bit images 8-bit, so they encode the same as before.
+    if img.color().bytes_per_pixel() == img.color().channel_count() {
+        DynamicImage::ImageRgba8(recolored.to_rgba8())
+    } else {
+        recolored
+    }
+}
+
+/// Encode `img`. JPEG has no alpha channel, so transparency is dropped; WebP is lossless
+/// unless given a quality.
+pub fn encode(img: &DynamicImage, format: OutputFormat, quality: Option<u8>) -> Result<Vec<u8>, AppError> {
+    let mut out = Cursor::new(Vec::new());
+    let result = match format {
+        // PNG has no float channels.
+        OutputFormat::Png if matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)) =>
+            DynamicImage::ImageRgba16(img.to_rgba16()).write_to(&mut out, ImageOutputFormat::Png),
+        OutputFormat::Png => img.write_to(&mut out, ImageOutputFormat::Png),
+        OutputFormat::Jpeg => JpegEncoder::new_with_quality(&mut out, quality.unwrap_or(DEFAULT_JPEG_QUALITY))
+            .encode_image(&img.to_rgb8()),
+        OutputFormat::Webp => {
+            let quality = quality.map_or_else(WebPQuality::lossless, WebPQuality::lossy);
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_with_quality(&mut out, quality)
+                .encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8)
+        },
+        OutputFormat::Gif => img.write_to(&mut out, ImageOutputFormat::Gif),
+    };
+    result.map_err(|e| AppError::Internal(format!("Unable to encode image: {}", e)))?;
+    Ok(out.into_inner())
+}
\ No newline at end of file
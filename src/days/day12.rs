@@ -4,8 +4,7 @@ use std::{
     time::Instant,
 };
 use axum::{
-    extract::{ Json, Path, State },
-    http::StatusCode,
+    extract::State,
     routing::{ get, post },
     Router,
 };
@@ -18,6 +17,8 @@ use serde::Serialize;
 use ulid::Ulid;
 use uuid::Uuid;
 
+use crate::{error::AppError, extract::{Json, Path}};
+
 #[derive(Default)]
 struct StringTimes {
     store: BTreeMap<String, Instant>,
@@ -46,30 +47,24 @@ async fn save_time(
 async fn load_time(
     State(string_times): State<Arc<Mutex<StringTimes>>>,
     Path(s): Path<String>
-) -> Result<String, (StatusCode, String)> {
+) -> Result<String, AppError> {
     let last_instant = {
         let string_times = string_times.lock()
             .expect("StringTimes lock should not be poisoned.");
         string_times.store.get(&s).map(Instant::to_owned)
     }.ok_or_else(||
-        (
-            StatusCode::NOT_FOUND,
-            format!("Cannot find time for string: {}", &s),
-        )
+        AppError::NotFound(format!("Cannot find time for string: {}", &s))
     )?;
     
     let elapsed = last_instant.elapsed();
     Ok(elapsed.as_secs().to_string())
 }
 
-async fn convert_ulids(Json(ulids): Json<Vec<String>>) -> Result<Json<Vec<String>>, (StatusCode, String)> {
+async fn convert_ulids(Json(ulids): Json<Vec<String>>) -> Result<Json<Vec<String>>, AppError> {
     let mut uuids = Vec::new();
 
     for encoded in ulids.iter().rev() {
-        let ulid = Ulid::from_string(encoded)
-            .map_err(
-                |e| (StatusCode::BAD_REQUEST, format!("Unable to parse Ulid: {}", e))
-            )?;
+        let ulid = Ulid::from_string(encoded)?;
         let uuid: Uuid = ulid.into();
         uuids.push(uuid.to_string());
     }
@@ -80,17 +75,14 @@ async fn convert_ulids(Json(ulids): Json<Vec<String>>) -> Result<Json<Vec<String
 async fn ulid_stats(
     Path(weekday): Path<u64>,
     Json(ulids): Json<Vec<String>>,
-) -> Result<Json<UlidStats>, (StatusCode, String)> {
+) -> Result<Json<UlidStats>, AppError> {
     let now_ulid = Ulid::new();
     let mut stats = UlidStats::default();
 
     let chrismas_eve = NaiveDate::from_ymd_opt(2023, 12, 24).unwrap();
 
     for encoded in ulids.iter().rev() {
-        let ulid = Ulid::from_string(encoded)
-            .map_err(
-                |e| (StatusCode::BAD_REQUEST, format!("Unable to parse Ulid: {}", e))
-            )?;
+        let ulid = Ulid::from_string(encoded)?;
         
         // Get the date from the Ulid.
         let ulid_time = NaiveDateTime::from_timestamp_millis(ulid.timestamp_ms() as i64)
@@ -1,11 +1,12 @@
 use axum::{
-    extract::Json,
     response::Html,
     routing::post,
     Router,
 };
 use serde::Deserialize;
 
+use crate::extract::Json;
+
 #[derive(Deserialize)]
 struct TemplateContent {
     content: String,
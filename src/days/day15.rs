@@ -1,5 +1,4 @@
 use axum::{
-    extract::Json,
     http::StatusCode,
     routing::post,
     Router,
@@ -7,6 +6,8 @@ use axum::{
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use crate::extract::Json;
+
 #[derive(Deserialize)]
 struct PasswordInput {
     input: String,
@@ -11,7 +11,7 @@ use std::{
 
 use axum::{
     extract::{
-        Path,
+        FromRef,
         State,
         WebSocketUpgrade,
         ws::{Message, WebSocket}
@@ -27,9 +27,12 @@ use futures_util::{
 use serde::{Deserialize, Serialize};
 use tokio::sync::{
     broadcast::Sender,
+    watch,
     RwLock
 };
 
+use crate::extract::Path;
+
 #[derive(Deserialize)]
 struct ChatInMessage {
     message: String,
@@ -47,18 +50,97 @@ struct ChatState {
     channels: HashMap<i64, Sender<Message>>,
 }
 
+#[derive(Clone, FromRef)]
+struct WsGamesState {
+    chat: Arc<RwLock<ChatState>>,
+    sessions: WsSessions,
+}
+
+/// Tracks open WebSocket sessions, so they can be closed and drained on shutdown.
+#[derive(Clone)]
+pub struct WsSessions {
+    shutdown: Arc<watch::Sender<bool>>,
+    active: Arc<watch::Sender<usize>>,
+}
+
+// Held by a session for as long as its socket is open.
+struct WsSessionGuard {
+    active: Arc<watch::Sender<usize>>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl WsSessions {
+    pub fn new() -> Self {
+        // Use send_replace/send_modify, since these senders may outlive every receiver.
+        Self {
+            shutdown: Arc::new(watch::channel(false).0),
+            active: Arc::new(watch::channel(0usize).0),
+        }
+    }
+
+    /// Number of sessions currently open.
+    pub fn active(&self) -> usize {
+        *self.active.borrow()
+    }
+
+    /// Signal every open session to close, then wait until all of them have.
+    pub async fn drain(&self) {
+        self.shutdown.send_replace(true);
+        let mut active = self.active.subscribe();
+        let _ = active.wait_for(|&n| n == 0).await;
+    }
+
+    fn open(&self) -> WsSessionGuard {
+        self.active.send_modify(|n| *n += 1);
+        WsSessionGuard {
+            active: self.active.clone(),
+            shutdown: self.shutdown.subscribe(),
+        }
+    }
+}
+
+impl Default for WsSessions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for WsSessionGuard {
+    fn drop(&mut self) {
+        self.active.send_modify(|n| *n -= 1);
+    }
+}
+
+// Resolves once the server starts draining sessions.
+async fn wait_for_shutdown(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|&s| s).await;
+}
+
 async fn ws_upgrade_ping(
+    State(sessions): State<WsSessions>,
     ws: WebSocketUpgrade,
 ) -> Response {
     tracing::info!("Starting ping websocket upgrade.");
-    ws.on_upgrade(|ws| handle_ping(ws))
+    let guard = sessions.open();
+    ws.on_upgrade(move |ws| handle_ping(ws, guard))
 }
 
-async fn handle_ping(mut ws: WebSocket) {
+async fn handle_ping(mut ws: WebSocket, mut guard: WsSessionGuard) {
     tracing::info!("Starting ping session.");
     let mut ping_state = false;
 
-    while let Some(ws_result) = ws.recv().await {
+    loop {
+        let ws_result = tokio::select! {
+            r = ws.recv() => match r {
+                Some(r) => r,
+                None => break,
+            },
+            _ = wait_for_shutdown(&mut guard.shutdown) => {
+                tracing::info!("Closing ping session for shutdown.");
+                let _ = ws.send(Message::Close(None)).await;
+                break;
+            },
+        };
         match ws_result {
             Ok(msg) => {
                 match msg {
@@ -103,15 +185,24 @@ async fn get_chat_views(State(chat_state): State<Arc<RwLock<ChatState>>>) -> Str
 
 async fn ws_upgrade_chat(
     State(chat_state): State<Arc<RwLock<ChatState>>>,
+    State(sessions): State<WsSessions>,
     Path((channel, user)): Path<(i64, String)>,
     ws: WebSocketUpgrade,
 ) -> Response {
     //tracing::info!("Starting chat websocket upgrade.");
-    ws.on_upgrade(move |ws| handle_chat(ws, chat_state, channel, user))
+    let guard = sessions.open();
+    ws.on_upgrade(move |ws| handle_chat(ws, chat_state, channel, user, guard))
 }
 
-async fn handle_chat(ws: WebSocket, chat_state: Arc<RwLock<ChatState>>, room: i64, user: String) {
+async fn handle_chat(
+    ws: WebSocket,
+    chat_state: Arc<RwLock<ChatState>>,
+    room: i64,
+    user: String,
+    guard: WsSessionGuard,
+) {
     let (mut ws_sink, mut ws_stream) = ws.split();
+    let mut shutdown = guard.shutdown.clone();
 
     let mut send_ws = {
         tracing::info!("Beginning session for channel: {room} user: {user}.");
@@ -134,7 +225,18 @@ async fn handle_chat(ws: WebSocket, chat_state: Arc<RwLock<ChatState>>, room: i6
         };
 
         let fut = tokio::spawn(async move {
-            while let Ok(msg) = receiver.recv().await {
+            loop {
+                let msg = tokio::select! {
+                    r = receiver.recv() => match r {
+                        Ok(msg) => msg,
+                        Err(_) => return,
+                    },
+                    _ = wait_for_shutdown(&mut shutdown) => {
+                        tracing::info!("Closing chat session for shutdown.");
+                        let _ = ws_sink.send(Message::Close(None)).await;
+                        return;
+                    },
+                };
                 if let Err(e) = ws_sink.send(msg).await {
                     tracing::info!("Disconnecting due to error: {e}");
                     return;
@@ -206,12 +308,15 @@ async fn handle_chat(ws: WebSocket, chat_state: Arc<RwLock<ChatState>>, room: i6
     tracing::info!("Closing websocket.");
 }
 
-pub fn ws_games_router() -> Router {
-    let chat_state = Arc::new(RwLock::new(ChatState::default()));
+pub fn ws_games_router(sessions: WsSessions) -> Router {
+    let state = WsGamesState {
+        chat: Arc::new(RwLock::new(ChatState::default())),
+        sessions,
+    };
 
     Router::new().route("/ws/ping", get(ws_upgrade_ping))
         .route("/reset", post(reset_chat))
         .route("/views", get(get_chat_views))
         .route("/ws/room/:channel/user/:user", get(ws_upgrade_chat))
-        .with_state(chat_state)
+        .with_state(state)
 }
\ No newline at end of file
@@ -2,7 +2,7 @@ use std::collections::{HashMap, HashSet};
 
 use axum::{
     body::Bytes,
-    http::{header::CONTENT_TYPE, Request, StatusCode},
+    http::{header::CONTENT_TYPE, Request},
     middleware::{from_fn, Next},
     response::IntoResponse,
     routing::post,
@@ -12,41 +12,38 @@ use flate2::read::ZlibDecoder;
 
 use tar::Archive;
 
+use crate::error::AppError;
+
 struct TreeEntry(u32, String, String);
 
-async fn tar_only_middleware<T>(request: Request<T>, next: Next<T>) -> Result<impl IntoResponse, StatusCode> {
+async fn tar_only_middleware<T>(request: Request<T>, next: Next<T>) -> Result<impl IntoResponse, AppError> {
     let content_type = request.headers().get(CONTENT_TYPE);
 
     // If content header matches, or is missing.
     if content_type.map(|h| h.to_str().unwrap_or("") == "application/x-tar").unwrap_or(true) {
         Ok(next.run(request).await)
     } else {
-        Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        Err(AppError::UnsupportedMediaType("Expected application/x-tar".into()))
     }
 }
 
-async fn file_count_in_tar(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
+async fn file_count_in_tar(tar_data: Bytes) -> Result<String, AppError> {
     let mut tar_archive = Archive::new(tar_data.as_ref());
-    tar_archive.entries()
-        .map(|es| es.count().to_string())
-        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Unable to parse tar: {}", e)))
+    let entries = tar_archive.entries()?;
+    Ok(entries.count().to_string())
 }
 
-async fn file_size_in_tar(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
+async fn file_size_in_tar(tar_data: Bytes) -> Result<String, AppError> {
     let mut tar_archive = Archive::new(tar_data.as_ref());
-    tar_archive.entries()
-        .map(
-            |es| {
-                es.filter_map(|e| e.ok())
-                    .map(|e| e.size())
-                    .sum::<u64>()
-                    .to_string()
-            }
-        ).map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Unable to parse tar: {}", e)))
+    let entries = tar_archive.entries()?;
+    Ok(entries.filter_map(|e| e.ok())
+        .map(|e| e.size())
+        .sum::<u64>()
+        .to_string())
 }
 
 #[axum::debug_handler]
-async fn find_cookie(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
+async fn find_cookie(tar_data: Bytes) -> Result<String, AppError> {
     let mut tar_archive = Archive::new(tar_data.as_ref());
     
     let mut objs = HashMap::<String, &[u8]>::new();
@@ -55,11 +52,11 @@ async fn find_cookie(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
     // First, get all of the files, and put them in a map.
     // Assumes uncompressed tar files, which are already allocated in Bytes.
     let entries = tar_archive.entries()
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to get archive entries: {}", e)))?;
+        .map_err(|e| AppError::BadRequest(format!("Unable to get archive entries: {}", e)))?;
     for entry_result in entries {
         match entry_result {
             Ok(entry) => {
-                let path = entry.path().unwrap();
+                let path = entry.path()?;
                 // Capture object files, with at least a SHA-1 HASH.
                 if path.starts_with(".git/objects/") && path.file_name().unwrap().len() == 38 {
                     let new_path = {
@@ -79,11 +76,11 @@ async fn find_cookie(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
                 else if path.to_str() == Some(".git/refs/heads/christmas") {
                     let position = entry.raw_file_position() as usize;
                     let tar_slice = &tar_data[position..position + entry.size() as usize - 1];
-                    ref_hash.push_str(&String::from_utf8_lossy(&tar_slice));
+                    ref_hash.push_str(&String::from_utf8_lossy(tar_slice));
                     //println!("Ref: {}", ref_hash);
                 }
             },
-            Err(e) => { return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("Unable to get entry: {}", e))); }
+            Err(e) => { return Err(AppError::UnprocessableEntity(format!("Unable to get entry: {}", e))); }
         }
     }
 
@@ -93,11 +90,11 @@ async fn find_cookie(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
 
     // Search through all commits to see which one has the right answer.
     tokio::task::block_in_place(|| {
-        while let Some(commit) = next_hash.clone().map(|r| objs.get(&r)).flatten() {
-            let commit = uncompress_obj(*commit)
-                .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Unable to decompress object: {}", e)))?;
+        while let Some(commit) = next_hash.clone().and_then(|r| objs.get(&r)) {
+            let commit = uncompress_obj(commit)
+                .map_err(|e| AppError::UnprocessableEntity(format!("Unable to decompress object: {}", e)))?;
             if !commit.starts_with(b"commit") {
-                return Err((StatusCode::UNPROCESSABLE_ENTITY, "Not a commit entry.".into()));
+                return Err(AppError::UnprocessableEntity("Not a commit entry.".into()));
             }
             // Split null-terminated part of string.
             let commit_string = commit.splitn(2, |&u| u == 0)
@@ -111,16 +108,16 @@ async fn find_cookie(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
                 .filter_map(|l| l.strip_prefix("tree "))
                 .map(str::to_string)
                 .next()
-                .ok_or_else(|| (StatusCode::UNPROCESSABLE_ENTITY, "Error finding tree in commit".into()))?;
+                .ok_or_else(|| AppError::UnprocessableEntity("Error finding tree in commit".into()))?;
             let author = commit_string.lines()
                 .filter_map(|l| l.strip_prefix("author "))
                 .filter_map(|l| l.split(" <").next()) // Extract all before email.
                 .map(str::to_string)
                 .next()
-                .ok_or_else(|| (StatusCode::UNPROCESSABLE_ENTITY, "Error finding author in commit".into()))?;
+                .ok_or_else(|| AppError::UnprocessableEntity("Error finding author in commit".into()))?;
 
             let result = find_file_with_cookie(&objs, &tree_hash, &found_set)
-                .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+                .map_err(AppError::UnprocessableEntity)?;
             if let Err(other_set) = result {
                 found_set.extend(other_set);
             } else {
@@ -133,7 +130,7 @@ async fn find_cookie(tar_data: Bytes) -> Result<String, (StatusCode, String)> {
                 .map(str::to_string)
                 .next();
         }
-        Err((StatusCode::NOT_FOUND, "Cookie not found.".into()))
+        Err(AppError::NotFound("Cookie not found.".into()))
     })
 }
 
@@ -155,7 +152,7 @@ fn find_file_with_cookie(
     let mut local_found_set = HashSet::new();
 
     if let Some(tree) = objs.get(tree_hash) {
-        let tree = uncompress_obj(*tree)
+        let tree = uncompress_obj(tree)
             .map_err(|e| format!("Unable to decompress tree: {}", e))?;
         if !tree.starts_with(b"tree ") {
             return Err("Cannot process tree.".into());
@@ -165,7 +162,7 @@ fn find_file_with_cookie(
             //println!("{} {} {}", flags, filename, hash);
             if filename.trim() == "santa.txt" {
                 if let Some(blob) = objs.get(&hash) {
-                    let blob = uncompress_obj(*blob)
+                    let blob = uncompress_obj(blob)
                         .map_err(|e| format!("Unable to decompress blob: {}", e))?;
                     if String::from_utf8_lossy(&blob).contains("COOKIE") {
                         return Ok(Ok(()));
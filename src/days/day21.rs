@@ -1,6 +1,7 @@
+use std::sync::Arc;
+
 use axum::{
-    extract::Path,
-    http::StatusCode,
+    extract::State,
     routing::get,
     Router,
 };
@@ -14,6 +15,11 @@ use s2::{
     cellid::CellID,
 };
 
+use crate::{error::AppError, extract::Path};
+
+/// Base URL of the public Photon geocoder.
+pub const PHOTON_BASE_URL: &str = "https://photon.komoot.io";
+
 fn degrees_to_dms(angle: f64, is_latitude: bool) -> (u64, u64, f64, char) {
     // Readjust latitude in case it wraps around the pole.
     let angle = if is_latitude && angle.abs() > 90.0 {
@@ -34,9 +40,9 @@ fn degrees_to_dms(angle: f64, is_latitude: bool) -> (u64, u64, f64, char) {
     (degrees, minutes, seconds, direction)
 }
 
-async fn get_coords_for_hilbert(Path(code): Path<String>) -> Result<String, (StatusCode, String)> {
+async fn get_coords_for_hilbert(Path(code): Path<String>) -> Result<String, AppError> {
     let code_num = u64::from_str_radix(&code, 2)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Cannot parse string: {}", e)))?;
+        .map_err(|e| AppError::BadRequest(format!("Cannot parse string: {}", e)))?;
 
     let s2_cell = Cell::from(CellID(code_num));
     let (lat, lon) = {
@@ -53,9 +59,12 @@ async fn get_coords_for_hilbert(Path(code): Path<String>) -> Result<String, (Sta
     )
 }
 
-async fn get_country_from_hilbert(Path(code): Path<String>) -> Result<String, (StatusCode, String)> {
+async fn get_country_from_hilbert(
+    State(photon_base): State<Arc<str>>,
+    Path(code): Path<String>,
+) -> Result<String, AppError> {
     let code_num = u64::from_str_radix(&code, 2)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Cannot parse string: {}", e)))?;
+        .map_err(|e| AppError::BadRequest(format!("Cannot parse string: {}", e)))?;
 
     let s2_cell = Cell::from(CellID(code_num));
     let (lat, lon) = {
@@ -65,9 +74,9 @@ async fn get_country_from_hilbert(Path(code): Path<String>) -> Result<String, (S
 
     tracing::info!("Parsed coordinates: {lat}, {lon}"); // To verify coordinates in other sources.
 
-    // Use https://photon.komoot.io/ for country lookup.
+    // Use Photon (https://photon.komoot.io/ by default) for country lookup.
     // Data provided by https://openstreetmap.org
-    let client = PhotonApiClient::default();
+    let client = PhotonApiClient::new(&photon_base);
     let request = tokio::task::spawn_blocking(move || client.reverse_search(
             LatLon::new(lat, lon), 
             Some(
@@ -75,19 +84,21 @@ async fn get_country_from_hilbert(Path(code): Path<String>) -> Result<String, (S
                     .radius(10)
                     .limit(1)
             )
-        ).map_err(|e| (StatusCode::BAD_GATEWAY, format!("Unable to process Photon data: {}", e)))
-    ).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Internal error: {}", e)))??;
+        ).map_err(|e| AppError::BadGateway(format!("Unable to process Photon data: {}", e)))
+    ).await.map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
 
     tracing::info!("Found results: {}", request.len());
 
     if let Some(Some(country)) = request.into_iter().next().map(|f| f.country.clone()) {
         Ok(country)
     } else {
-        Err((StatusCode::NOT_FOUND, "Unable to find country data.".into()))
+        Err(AppError::NotFound("Unable to find country data.".into()))
     }
 }
 
-pub fn world_coord_router() -> Router {
+/// `photon_base` is the Photon server used for country lookups.
+pub fn world_coord_router(photon_base: &str) -> Router {
     Router::new().route("/coords/:bin", get(get_coords_for_hilbert))
         .route("/country/:bin", get(get_country_from_hilbert))
+        .with_state(Arc::from(photon_base))
 }
\ No newline at end of file
@@ -8,6 +8,8 @@ use axum::{
     Router,
 };
 
+use crate::error::AppError;
+
 async fn find_only_integer(input: String) -> String {
     // Since x XOR x = 0, we can find the unique single integer by xoring the entire input.
     let input = input.lines()
@@ -16,8 +18,7 @@ async fn find_only_integer(input: String) -> String {
 
     // Repeatedly write the present emoji to the output string.
     let present = '🎁';
-    std::iter::repeat(present)
-        .take(input as usize)
+    std::iter::repeat_n(present, input as usize)
         .collect::<String>()
 }
 
@@ -76,22 +77,22 @@ fn find_shortest_path_and_distance(
     None
 }
 
-async fn find_rocket_path(input: String) -> Result<String, String> {
+async fn find_rocket_path(input: String) -> Result<String, AppError> {
     let mut lines = input.lines();
 
     // First, get the star coords.
     let star_count = match lines.next() {
         Some(v) => v,
-        None => { return Err("No input".into());}
+        None => { return Err(AppError::BadRequest("No input".into()));}
     };
     let star_count = star_count.parse::<usize>()
-        .map_err(|e| format!("Parsing error: {}", e))?;
+        .map_err(|e| AppError::BadRequest(format!("Parsing error: {}", e)))?;
 
     let mut star_coords = vec![];
     for l in std::iter::repeat_with(|| lines.next()).take(star_count) {
         let coord_str = match l {
             Some(v) => v,
-            None => { return Err("No input".into());}
+            None => { return Err(AppError::BadRequest("No input".into()));}
         };
 
         let coord_vals = coord_str.split_ascii_whitespace()
@@ -99,7 +100,7 @@ async fn find_rocket_path(input: String) -> Result<String, String> {
             .collect::<Vec<i32>>();
 
         if coord_vals.len() != 3 {
-            return Err(format!("Failed parsing coord string: {}", coord_str));
+            return Err(AppError::BadRequest(format!("Failed parsing coord string: {}", coord_str)));
         }
 
         star_coords.push(SpaceCoord(coord_vals[0], coord_vals[1], coord_vals[2]));
@@ -108,16 +109,16 @@ async fn find_rocket_path(input: String) -> Result<String, String> {
     // Then, get the teleportation paths.
     let portal_count = match lines.next() {
         Some(v) => v,
-        None => { return Err("No input".into());}
+        None => { return Err(AppError::BadRequest("No input".into()));}
     };
     let portal_count = portal_count.parse::<usize>()
-        .map_err(|e| format!("Parsing error: {}", e))?;
+        .map_err(|e| AppError::BadRequest(format!("Parsing error: {}", e)))?;
 
     let mut portal_paths = HashMap::<usize, Vec<(usize, f32)>>::new();
     for l in std::iter::repeat_with(|| lines.next()).take(portal_count) {
         let portal_str = match l {
             Some(v) => v,
-            None => { return Err("No input".into());}
+            None => { return Err(AppError::BadRequest("No input".into()));}
         };
 
         let portal_vals = portal_str.split_ascii_whitespace()
@@ -125,17 +126,21 @@ async fn find_rocket_path(input: String) -> Result<String, String> {
             .collect::<Vec<usize>>();
 
         if portal_vals.len() != 2 {
-            return Err(format!("Failed parsing coord string: {}", portal_str));
+            return Err(AppError::BadRequest(format!("Failed parsing coord string: {}", portal_str)));
         }
 
         // Calculate distance.
-        let dist = distance(star_coords[portal_vals[0]], star_coords[portal_vals[1]]);
+        let (src, dest) = match (star_coords.get(portal_vals[0]), star_coords.get(portal_vals[1])) {
+            (Some(&src), Some(&dest)) => (src, dest),
+            _ => { return Err(AppError::BadRequest(format!("Unknown star in portal: {}", portal_str))); }
+        };
+        let dist = distance(src, dest);
         // Create bi-directional entry.
         portal_paths.entry(portal_vals[0])
-            .or_insert_with(Vec::new)
+            .or_default()
             .push((portal_vals[1], dist));
         portal_paths.entry(portal_vals[1])
-            .or_insert_with(Vec::new)
+            .or_default()
             .push((portal_vals[0], dist));
     }
 
@@ -144,7 +149,7 @@ async fn find_rocket_path(input: String) -> Result<String, String> {
     // First, get the shortest portal path.
     let (shortest_len, dist) = match find_shortest_path_and_distance(&portal_paths, star_count.saturating_sub(1)){
         Some(v) => v,
-        None => { return Err("Could not find path.".into());}
+        None => { return Err(AppError::NotFound("Could not find path.".into()));}
     };
 
     // Return count and distance with 3 decimal precision.
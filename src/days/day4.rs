@@ -1,30 +1,22 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use axum::{
-    extract::Json,
-    http::StatusCode,
-    routing::post,
+    extract::{BodyStream, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
     Router,
 };
 
+use futures::TryStreamExt;
 use serde::{Serialize, Deserialize};
 
-#[derive(Deserialize)]
-#[allow(dead_code, unused_variables)]
-struct ReindeerStrength {
-    name: String,
-    strength: u64,
-    #[serde(default)]
-    speed: f64,
-    #[serde(default)]
-    height: u64,
-    #[serde(default)]
-    antler_width: u64,
-    #[serde(default)]
-    snow_magic_power: u64,
-    #[serde(default)]
-    favorite_food: String,
-    #[serde(rename="cAnD13s_3ATeN-yesT3rdAy", default)]
-    candies_eaten_yesterday: u64
-}
+use super::reindeer_db::{ContestRecord, Reindeer, ReindeerDb, StoredReindeer};
+use crate::{error::AppError, extract::{Json, Path, Query}};
+
+mod contest;
+mod input;
+mod stats;
+use contest::{Category, Ranking};
 
 #[derive(Serialize)]
 struct ContestResults {
@@ -34,39 +26,208 @@ struct ContestResults {
     consumer: String
 }
 
-async fn strength(Json(reindeer): Json<Vec<ReindeerStrength>>) -> String {
-    reindeer.iter()
-        .map(|r| r.strength)
-        .sum::<u64>()
-        .to_string()
+fn validate_all(reindeer: &[Reindeer]) -> Result<(), AppError> {
+    reindeer.iter().try_for_each(Reindeer::validate)
+}
+
+// Sums as the rows arrive, so large CSV and NDJSON herds are never held in memory.
+async fn strength(headers: HeaderMap, body: BodyStream) -> Result<String, AppError> {
+    let total = input::reindeer_stream(&headers, body).await?
+        .try_fold(0i64, |total, r| async move {
+            total.checked_add(r.strength)
+                .ok_or_else(|| AppError::UnprocessableEntity("Total strength overflowed".into()))
+        })
+        .await?;
+    Ok(total.to_string())
+}
+
+#[derive(Deserialize)]
+struct StatsParams {
+    buckets: Option<usize>,
+    /// Comma-separated, e.g. `5,50,95`.
+    percentiles: Option<String>,
+}
+
+async fn herd_stats(
+    Query(params): Query<StatsParams>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> Result<Json<stats::HerdStats>, AppError> {
+    let percentiles = match params.percentiles {
+        Some(percentiles) => percentiles.split(',')
+            .map(|p| p.trim().parse::<f64>()
+                .map_err(|_| AppError::BadRequest(format!("Invalid percentile: {}", p))))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => stats::DEFAULT_PERCENTILES.to_vec(),
+    };
+    // Median and percentiles need every value, so the herd is collected first.
+    let reindeer = input::collect_reindeer(&headers, body).await?;
+    let buckets = params.buckets.unwrap_or(stats::DEFAULT_BUCKETS);
+    Ok(Json(stats::herd_stats(&reindeer, buckets, &percentiles)?))
 }
 
-async fn contest(Json(reindeer): Json<Vec<ReindeerStrength>>)
-    -> Result<Json<ContestResults>, StatusCode> {
-    if reindeer.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+// Rank the reindeer and record the winner of each category.
+async fn hold_contest(
+    reindeer_db: &dyn ReindeerDb,
+    categories: &[Category],
+    reindeer: &[Reindeer],
+) -> Result<Vec<Ranking>, AppError> {
+    let rankings = contest::run(categories, reindeer)?;
+    let winners: BTreeMap<String, String> = rankings.iter()
+        .map(|ranking| (ranking.category.clone(), ranking.places[0].name.clone()))
+        .collect();
+    reindeer_db.record_contest(&winners).await?;
+    Ok(rankings)
+}
+
+// The original response: only the winning message of each default category.
+async fn hold_default_contest(
+    reindeer_db: &dyn ReindeerDb,
+    reindeer: &[Reindeer],
+) -> Result<ContestResults, AppError> {
+    // The consumer's message names their favorite food, so they must have one.
+    if let Some(consumer) = reindeer.iter().max_by_key(|r| r.candies_eaten_yesterday) {
+        if consumer.favorite_food.is_empty() {
+            return Err(AppError::BadRequest(format!("{} has no favorite food", &consumer.name)));
+        }
     }
 
-    let fastest = reindeer.iter()
-        .max_by(|a, b| a.speed.total_cmp(&b.speed))
-        .unwrap();
-    let tallest = reindeer.iter().max_by_key(|r| r.height).unwrap();
-    let magician = reindeer.iter().max_by_key(|r| r.snow_magic_power).unwrap();
-    let most_candies = reindeer.iter().max_by_key(|r| r.candies_eaten_yesterday).unwrap();
+    let rankings = hold_contest(reindeer_db, &contest::default_categories(), reindeer).await?;
+    let winning_message = |category: &str| rankings.iter()
+        .find(|ranking| ranking.category == category)
+        .map(|ranking| ranking.places[0].message.clone())
+        .expect("Default categories should all be ranked.");
+    Ok(ContestResults {
+        fastest: winning_message("fastest"),
+        tallest: winning_message("tallest"),
+        magician: winning_message("magician"),
+        consumer: winning_message("consumer"),
+    })
+}
+
+async fn stored_reindeer(reindeer_db: &dyn ReindeerDb) -> Result<Vec<Reindeer>, AppError> {
+    Ok(reindeer_db.list_reindeer().await?
+        .into_iter()
+        .map(|stored| stored.reindeer)
+        .collect())
+}
+
+async fn contest(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> Result<Json<ContestResults>, AppError> {
+    let reindeer = input::collect_reindeer(&headers, body).await?;
+    Ok(Json(hold_default_contest(reindeer_db.as_ref(), &reindeer).await?))
+}
 
-    if most_candies.favorite_food.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+// Hold a contest between every reindeer in the registry.
+async fn stored_contest(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+) -> Result<Json<ContestResults>, AppError> {
+    let reindeer = stored_reindeer(reindeer_db.as_ref()).await?;
+    Ok(Json(hold_default_contest(reindeer_db.as_ref(), &reindeer).await?))
+}
+
+#[derive(Deserialize)]
+struct RankedContest {
+    /// Defaults to the original four categories.
+    categories: Option<Vec<Category>>,
+    /// Defaults to every reindeer in the registry.
+    reindeer: Option<Vec<Reindeer>>,
+}
+
+async fn ranked_contest(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    Json(spec): Json<RankedContest>,
+) -> Result<Json<Vec<Ranking>>, AppError> {
+    let reindeer = match spec.reindeer {
+        Some(reindeer) => {
+            validate_all(&reindeer)?;
+            reindeer
+        },
+        None => stored_reindeer(reindeer_db.as_ref()).await?,
+    };
+    let categories = spec.categories.unwrap_or_else(contest::default_categories);
+    Ok(Json(hold_contest(reindeer_db.as_ref(), &categories, &reindeer).await?))
+}
+
+#[derive(Deserialize)]
+struct HistoryParams {
+    limit: Option<i64>,
+}
+
+const DEFAULT_HISTORY_LIMIT: i64 = 20;
+
+async fn contest_history(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    Query(params): Query<HistoryParams>,
+) -> Result<Json<Vec<ContestRecord>>, AppError> {
+    let limit = params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if limit < 0 {
+        return Err(AppError::BadRequest("Limit must not be negative".into()));
     }
+    Ok(Json(reindeer_db.contest_history(limit).await?))
+}
+
+fn reindeer_not_found(id: i64) -> AppError {
+    AppError::NotFound(format!("No reindeer with id {}", id))
+}
+
+async fn create_reindeer(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    Json(reindeer): Json<Reindeer>,
+) -> Result<(StatusCode, Json<StoredReindeer>), AppError> {
+    reindeer.validate()?;
+    let stored = reindeer_db.create_reindeer(&reindeer).await?;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+async fn list_reindeer(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+) -> Result<Json<Vec<StoredReindeer>>, AppError> {
+    Ok(Json(reindeer_db.list_reindeer().await?))
+}
 
-    Ok(Json(ContestResults {
-        fastest: format!("Speeding past the finish line with a strength of {} is {}", fastest.strength, &fastest.name),
-        tallest: format!("{} is standing tall with his {} cm wide antlers", &tallest.name, tallest.antler_width),
-        magician: format!("{} could blast you away with a snow magic power of {}", &magician.name, magician.snow_magic_power),
-        consumer: format!("{} ate lots of candies, but also some {}", &most_candies.name, &most_candies.favorite_food)
-    }))
+async fn get_reindeer(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    Path(id): Path<i64>,
+) -> Result<Json<StoredReindeer>, AppError> {
+    reindeer_db.get_reindeer(id).await?
+        .map(Json)
+        .ok_or_else(|| reindeer_not_found(id))
+}
+
+async fn update_reindeer(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    Path(id): Path<i64>,
+    Json(reindeer): Json<Reindeer>,
+) -> Result<Json<StoredReindeer>, AppError> {
+    reindeer.validate()?;
+    reindeer_db.update_reindeer(id, &reindeer).await?
+        .map(Json)
+        .ok_or_else(|| reindeer_not_found(id))
+}
+
+async fn delete_reindeer(
+    State(reindeer_db): State<Arc<dyn ReindeerDb>>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    if reindeer_db.delete_reindeer(id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(reindeer_not_found(id))
+    }
 }
 
-pub fn serdeer_router() -> Router {
+pub fn serdeer_router(reindeer_db: Arc<dyn ReindeerDb>) -> Router {
     Router::new().route("/strength", post(strength))
+        .route("/stats", post(herd_stats))
         .route("/contest", post(contest))
+        .route("/contest/stored", post(stored_contest))
+        .route("/contest/ranked", post(ranked_contest))
+        .route("/contest/history", get(contest_history))
+        .route("/reindeer", get(list_reindeer).post(create_reindeer))
+        .route("/reindeer/:id", get(get_reindeer).put(update_reindeer).delete(delete_reindeer))
+        .with_state(reindeer_db)
 }
\ No newline at end of file
@@ -0,0 +1,231 @@
+//! Reindeer contests defined by a JSON spec of categories.
+use std::{cmp::Ordering, collections::HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::days::reindeer_db::Reindeer;
+use crate::error::AppError;
+
+/// Something to rank reindeer by. `position` is the order the reindeer were given in.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Name,
+    Strength,
+    Speed,
+    Height,
+    AntlerWidth,
+    SnowMagicPower,
+    FavoriteFood,
+    #[serde(alias = "cAnD13s_3ATeN-yesT3rdAy")]
+    CandiesEatenYesterday,
+    Position,
+}
+
+const TEMPLATE_FIELDS: [(&str, Field); 9] = [
+    ("name", Field::Name),
+    ("strength", Field::Strength),
+    ("speed", Field::Speed),
+    ("height", Field::Height),
+    ("antler_width", Field::AntlerWidth),
+    ("snow_magic_power", Field::SnowMagicPower),
+    ("favorite_food", Field::FavoriteFood),
+    ("candies_eaten_yesterday", Field::CandiesEatenYesterday),
+    ("position", Field::Position),
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl FieldValue {
+    fn cmp(&self, other: &FieldValue) -> Ordering {
+        match (self, other) {
+            (FieldValue::Int(a), FieldValue::Int(b)) => a.cmp(b),
+            (FieldValue::Float(a), FieldValue::Float(b)) => a.total_cmp(b),
+            (FieldValue::Text(a), FieldValue::Text(b)) => a.cmp(b),
+            _ => unreachable!("A field should always have the same kind of value."),
+        }
+    }
+
+    fn render(&self) -> String {
+        match self {
+            FieldValue::Int(n) => n.to_string(),
+            FieldValue::Float(x) => x.to_string(),
+            FieldValue::Text(s) => s.clone(),
+        }
+    }
+}
+
+impl Field {
+    pub fn value(self, reindeer: &Reindeer, position: usize) -> FieldValue {
+        match self {
+            Field::Name => FieldValue::Text(reindeer.name.clone()),
+            Field::Strength => FieldValue::Int(reindeer.strength),
+            Field::Speed => FieldValue::Float(reindeer.speed),
+            Field::Height => FieldValue::Int(reindeer.height),
+            Field::AntlerWidth => FieldValue::Int(reindeer.antler_width),
+            Field::SnowMagicPower => FieldValue::Int(reindeer.snow_magic_power),
+            Field::FavoriteFood => FieldValue::Text(reindeer.favorite_food.clone()),
+            Field::CandiesEatenYesterday => FieldValue::Int(reindeer.candies_eaten_yesterday),
+            Field::Position => FieldValue::Int(position as i64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SortKey {
+    pub field: Field,
+    #[serde(default)]
+    pub order: Direction,
+}
+
+/// One category of a contest. Reindeer still tied after every tie-break share a place.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Category {
+    pub name: String,
+    #[serde(flatten)]
+    pub key: SortKey,
+    #[serde(default)]
+    pub tie_break: Vec<SortKey>,
+    /// Message for each place, with `{field}`, `{place}` and `{value}` placeholders.
+    pub template: String,
+}
+
+// The original four categories, with their original messages and the last reindeer
+// winning ties.
+const DEFAULT_SPEC: &str = r#"[
+    {
+        "name": "fastest", "field": "speed", "tie_break": [{ "field": "position" }],
+        "template": "Speeding past the finish line with a strength of {strength} is {name}"
+    },
+    {
+        "name": "tallest", "field": "height", "tie_break": [{ "field": "position" }],
+        "template": "{name} is standing tall with his {antler_width} cm wide antlers"
+    },
+    {
+        "name": "magician", "field": "snow_magic_power", "tie_break": [{ "field": "position" }],
+        "template": "{name} could blast you away with a snow magic power of {snow_magic_power}"
+    },
+    {
+        "name": "consumer", "field": "candies_eaten_yesterday", "tie_break": [{ "field": "position" }],
+        "template": "{name} ate lots of candies, but also some {favorite_food}"
+    }
+]"#;
+
+pub fn default_categories() -> Vec<Category> {
+    serde_json::from_str(DEFAULT_SPEC).expect("Default contest spec should be valid.")
+}
+
+#[derive(Serialize)]
+pub struct Place {
+    pub place: usize,
+    pub name: String,
+    pub value: FieldValue,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct Ranking {
+    pub category: String,
+    pub places: Vec<Place>,
+}
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Field(Field),
+    Place,
+    Value,
+}
+
+// Split a template into literals and placeholders, rejecting unknown placeholders.
+fn parse_template(template: &str) -> Result<Vec<Segment<'_>>, AppError> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}')
+            .map(|i| open + i)
+            .ok_or_else(|| AppError::BadRequest(format!("Unclosed placeholder in template: {}", template)))?;
+        segments.push(Segment::Literal(&rest[..open]));
+        segments.push(match &rest[open + 1..close] {
+            "place" => Segment::Place,
+            "value" => Segment::Value,
+            name => TEMPLATE_FIELDS.iter()
+                .find(|(n, _)| *n == name)
+                .map(|&(_, field)| Segment::Field(field))
+                .ok_or_else(|| AppError::BadRequest(format!("Unknown placeholder: {{{}}}", name)))?,
+        });
+        rest = &rest[close + 1..];
+    }
+    segments.push(Segment::Literal(rest));
+    Ok(segments)
+}
+
+fn compare(keys: &[&SortKey], a: (usize, &Reindeer), b: (usize, &Reindeer)) -> Ordering {
+    keys.iter()
+        .map(|key| {
+            let ordering = key.field.value(a.1, a.0).cmp(&key.field.value(b.1, b.0));
+            match key.order {
+                Direction::Ascending => ordering,
+                Direction::Descending => ordering.reverse(),
+            }
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Rank every reindeer in every category, best first.
+pub fn run(categories: &[Category], reindeer: &[Reindeer]) -> Result<Vec<Ranking>, AppError> {
+    if reindeer.is_empty() {
+        return Err(AppError::BadRequest("No reindeer in contest".into()));
+    }
+    if categories.is_empty() {
+        return Err(AppError::BadRequest("No categories in contest".into()));
+    }
+    let mut names = HashSet::new();
+    if let Some(category) = categories.iter().find(|c| !names.insert(c.name.as_str())) {
+        return Err(AppError::BadRequest(format!("Duplicate category: {}", category.name)));
+    }
+
+    categories.iter().map(|category| {
+        let template = parse_template(&category.template)?;
+        let keys: Vec<&SortKey> = std::iter::once(&category.key)
+            .chain(&category.tie_break)
+            .collect();
+
+        let mut order: Vec<(usize, &Reindeer)> = reindeer.iter().enumerate().collect();
+        order.sort_by(|&a, &b| compare(&keys, a, b));
+
+        let mut places: Vec<Place> = Vec::with_capacity(order.len());
+        for (i, &(position, r)) in order.iter().enumerate() {
+            // Competition ranking: reindeer tied on every key share a place, and the next is skipped.
+            let place = match places.last() {
+                Some(last) if compare(&keys, order[i - 1], (position, r)).is_eq() => last.place,
+                _ => i + 1,
+            };
+            let value = category.key.field.value(r, position);
+            let message = template.iter()
+                .map(|segment| match segment {
+                    Segment::Literal(s) => s.to_string(),
+                    Segment::Field(field) => field.value(r, position).render(),
+                    Segment::Place => place.to_string(),
+                    Segment::Value => value.render(),
+                })
+                .collect();
+            places.push(Place { place, name: r.name.clone(), value, message });
+        }
+        Ok(Ranking { category: category.name.clone(), places })
+    }).collect()
+}
\ No newline at end of file
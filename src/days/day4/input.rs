@@ -0,0 +1,135 @@
+//! Reindeer from a JSON array, CSV with a header row, or newline-delimited JSON.
+//!
+//! CSV and NDJSON bodies are read one row at a time, so a herd never has to fit in memory,
+//! unless it is collected for an endpoint that needs every reindeer at once.
+use axum::{
+    extract::BodyStream,
+    http::{header::CONTENT_TYPE, HeaderMap},
+};
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use tokio_util::{
+    codec::{FramedRead, LinesCodec, LinesCodecError},
+    io::StreamReader,
+};
+
+use crate::days::reindeer_db::Reindeer;
+use crate::error::AppError;
+
+pub type ReindeerStream = BoxStream<'static, Result<Reindeer, AppError>>;
+
+/// Largest JSON array accepted, as it has to be read whole. Matches axum's default body limit.
+const JSON_BODY_LIMIT: usize = 2 * 1024 * 1024;
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+/// Largest herd collected into memory, as streamed bodies are not held to the body limit.
+pub const MAX_COLLECTED_REINDEER: usize = 100_000;
+
+fn invalid_record(row: u64, column: Option<u64>, message: impl ToString) -> AppError {
+    AppError::InvalidRecord { row, column, message: message.to_string() }
+}
+
+// Validation errors from a streamed row are reported against that row.
+fn validated(row: u64, reindeer: Reindeer) -> Result<Reindeer, AppError> {
+    reindeer.validate()
+        .map(|_| reindeer)
+        .map_err(|e| invalid_record(row, None, e))
+}
+
+/// Reindeer from the request body, in the format given by its `Content-Type`.
+pub async fn reindeer_stream(headers: &HeaderMap, body: BodyStream) -> Result<ReindeerStream, AppError> {
+    let content_type = headers.get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split(';').next())
+        .map(|h| h.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let reader = StreamReader::new(body.map_err(std::io::Error::other));
+    match content_type.as_str() {
+        "application/json" => json_array(body_bytes(reader).await?),
+        "text/csv" => Ok(csv(reader)),
+        "application/x-ndjson" | "application/ndjson" => Ok(ndjson(reader)),
+        _ => Err(AppError::UnsupportedMediaType(format!(
+            "Expected application/json, text/csv or application/x-ndjson, got {:?}", content_type
+        ))),
+    }
+}
+
+/// Every reindeer in the request body, up to `MAX_COLLECTED_REINDEER`.
+pub async fn collect_reindeer(headers: &HeaderMap, body: BodyStream) -> Result<Vec<Reindeer>, AppError> {
+    let mut stream = reindeer_stream(headers, body).await?;
+    let mut reindeer = Vec::new();
+    while let Some(r) = stream.try_next().await? {
+        if reindeer.len() == MAX_COLLECTED_REINDEER {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Herds are limited to {} reindeer; use /strength for larger ones", MAX_COLLECTED_REINDEER
+            )));
+        }
+        reindeer.push(r);
+    }
+    Ok(reindeer)
+}
+
+async fn body_bytes(reader: impl tokio::io::AsyncRead + Unpin) -> Result<Vec<u8>, AppError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut bytes = Vec::new();
+    reader.take(JSON_BODY_LIMIT as u64 + 1).read_to_end(&mut bytes).await?;
+    if bytes.len() > JSON_BODY_LIMIT {
+        return Err(AppError::PayloadTooLarge(format!(
+            "JSON bodies are limited to {} bytes; use CSV or NDJSON for large herds", JSON_BODY_LIMIT
+        )));
+    }
+    Ok(bytes)
+}
+
+fn json_array(bytes: Vec<u8>) -> Result<ReindeerStream, AppError> {
+    let reindeer: Vec<Reindeer> = serde_json::from_slice(&bytes)
+        .map_err(|e| invalid_record(e.line() as u64, Some(e.column() as u64), e))?;
+    reindeer.iter().try_for_each(Reindeer::validate)?;
+    Ok(stream::iter(reindeer.into_iter().map(Ok)).boxed())
+}
+
+fn csv(reader: impl tokio::io::AsyncRead + Unpin + Send + 'static) -> ReindeerStream {
+    csv_async::AsyncDeserializer::from_reader(reader)
+        .into_deserialize_with_pos::<Reindeer>()
+        .map(|(result, pos)| {
+            let row = pos.line();
+            let reindeer = result.map_err(|e| csv_error(row, e))?;
+            validated(row, reindeer)
+        })
+        .boxed()
+}
+
+fn csv_error(row: u64, e: csv_async::Error) -> AppError {
+    // Columns are reported 1-based, like rows.
+    let column = match e.kind() {
+        csv_async::ErrorKind::Deserialize { err, .. } => err.field().map(|f| f + 1),
+        csv_async::ErrorKind::Utf8 { err, .. } => Some(err.field() as u64 + 1),
+        _ => None,
+    };
+    match e.kind() {
+        csv_async::ErrorKind::Io(_) => AppError::Io(e.into()),
+        csv_async::ErrorKind::Deserialize { err, .. } => invalid_record(row, column, err.kind()),
+        _ => invalid_record(row, column, e),
+    }
+}
+
+fn ndjson(reader: impl tokio::io::AsyncRead + Unpin + Send + 'static) -> ReindeerStream {
+    FramedRead::new(reader, LinesCodec::new_with_max_length(MAX_LINE_LENGTH))
+        .enumerate()
+        .filter(|(_, line)| std::future::ready(!matches!(line, Ok(l) if l.trim().is_empty())))
+        .map(|(i, line)| {
+            let row = i as u64 + 1;
+            let line = line.map_err(|e| match e {
+                LinesCodecError::MaxLineLengthExceeded =>
+                    invalid_record(row, None, format!("Line longer than {} bytes", MAX_LINE_LENGTH)),
+                LinesCodecError::Io(e) => AppError::Io(e),
+            })?;
+            let reindeer = serde_json::from_str(&line)
+                .map_err(|e| invalid_record(row, Some(e.column() as u64), e))?;
+            validated(row, reindeer)
+        })
+        .boxed()
+}
\ No newline at end of file
@@ -0,0 +1,142 @@
+//! Aggregates over a herd, for `/4/stats`.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use super::contest::{Field, FieldValue};
+use crate::days::reindeer_db::Reindeer;
+use crate::error::AppError;
+
+const NUMERIC_FIELDS: [(&str, Field); 6] = [
+    ("strength", Field::Strength),
+    ("speed", Field::Speed),
+    ("height", Field::Height),
+    ("antler_width", Field::AntlerWidth),
+    ("snow_magic_power", Field::SnowMagicPower),
+    ("candies_eaten_yesterday", Field::CandiesEatenYesterday),
+];
+
+pub const DEFAULT_BUCKETS: usize = 10;
+pub const MAX_BUCKETS: usize = 1000;
+pub const DEFAULT_PERCENTILES: [f64; 5] = [25.0, 75.0, 90.0, 95.0, 99.0];
+
+#[derive(Serialize)]
+pub struct Bucket {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+#[derive(Serialize)]
+pub struct FieldStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    /// Population standard deviation.
+    pub std_dev: f64,
+    /// Keyed by percentile, e.g. `"95"`.
+    pub percentiles: BTreeMap<String, f64>,
+    pub histogram: Vec<Bucket>,
+}
+
+#[derive(Serialize)]
+pub struct FoodCount {
+    pub food: String,
+    pub count: usize,
+}
+
+#[derive(Serialize)]
+pub struct HerdStats {
+    pub count: usize,
+    pub fields: BTreeMap<&'static str, FieldStats>,
+    /// Most popular first. Reindeer without a favorite food are left out.
+    pub favorite_foods: Vec<FoodCount>,
+}
+
+// Linear interpolation between the closest ranks, as in most spreadsheets.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+// Equal-width buckets from min to max; the last bucket includes max.
+fn histogram(sorted: &[f64], buckets: usize) -> Vec<Bucket> {
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    if min == max {
+        return vec![Bucket { start: min, end: max, count: sorted.len() }];
+    }
+
+    let width = (max - min) / buckets as f64;
+    let mut histogram: Vec<Bucket> = (0..buckets)
+        .map(|i| Bucket {
+            start: min + width * i as f64,
+            end: if i + 1 == buckets { max } else { min + width * (i + 1) as f64 },
+            count: 0,
+        })
+        .collect();
+    for &value in sorted {
+        let i = (((value - min) / width) as usize).min(buckets - 1);
+        histogram[i].count += 1;
+    }
+    histogram
+}
+
+fn field_stats(mut values: Vec<f64>, buckets: usize, percentiles: &[f64]) -> FieldStats {
+    values.sort_by(f64::total_cmp);
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+
+    FieldStats {
+        count: values.len(),
+        min: values[0],
+        max: values[values.len() - 1],
+        mean,
+        median: percentile(&values, 50.0),
+        std_dev: variance.sqrt(),
+        percentiles: percentiles.iter()
+            .map(|&p| (p.to_string(), percentile(&values, p)))
+            .collect(),
+        histogram: histogram(&values, buckets),
+    }
+}
+
+pub fn herd_stats(reindeer: &[Reindeer], buckets: usize, percentiles: &[f64]) -> Result<HerdStats, AppError> {
+    if reindeer.is_empty() {
+        return Err(AppError::BadRequest("No reindeer in herd".into()));
+    }
+    if !(1..=MAX_BUCKETS).contains(&buckets) {
+        return Err(AppError::BadRequest(format!("Bucket count must be between 1 and {}", MAX_BUCKETS)));
+    }
+    if let Some(p) = percentiles.iter().find(|p| !(0.0..=100.0).contains(*p)) {
+        return Err(AppError::BadRequest(format!("Percentile must be between 0 and 100: {}", p)));
+    }
+
+    let fields = NUMERIC_FIELDS.iter()
+        .map(|&(name, field)| {
+            let values = reindeer.iter()
+                .enumerate()
+                .map(|(position, r)| match field.value(r, position) {
+                    FieldValue::Int(n) => n as f64,
+                    FieldValue::Float(x) => x,
+                    FieldValue::Text(_) => unreachable!("Numeric fields should have numeric values."),
+                })
+                .collect();
+            (name, field_stats(values, buckets, percentiles))
+        })
+        .collect();
+
+    let mut foods = HashMap::<&str, usize>::new();
+    for r in reindeer.iter().filter(|r| !r.favorite_food.is_empty()) {
+        *foods.entry(r.favorite_food.as_str()).or_default() += 1;
+    }
+    let mut favorite_foods: Vec<FoodCount> = foods.into_iter()
+        .map(|(food, count)| FoodCount { food: food.to_string(), count })
+        .collect();
+    favorite_foods.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.food.cmp(&b.food)));
+
+    Ok(HerdStats { count: reindeer.len(), fields, favorite_foods })
+}
\ No newline at end of file
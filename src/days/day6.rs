@@ -1,9 +1,25 @@
+use std::{collections::BTreeMap, ops::Range};
+
 use axum::{
-    extract::Json,
+    body::Body,
+    error_handling::HandleErrorLayer,
+    extract::BodyStream,
+    response::{IntoResponse, Response},
     routing::post,
+    BoxError,
     Router,
 };
-use serde::Serialize;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tower::ServiceBuilder;
+use tower_http::decompression::{DecompressionBody, RequestDecompressionLayer};
+
+use crate::{error::AppError, extract::{Json, Query}};
+
+mod engine;
+mod highlight;
+use engine::{Match, MatchOptions, PatternSpec, PhraseMatcher};
+use highlight::{Marker, Style};
 
 #[derive(Default, Serialize)]
 struct ElfCounts {
@@ -14,21 +30,146 @@ struct ElfCounts {
     shelf_without_elf: u64
 }
 
-async fn count_elves(phrase: String) -> Json<ElfCounts> {
-    let mut elf_counts = ElfCounts::default();
-    elf_counts.elf = phrase.matches("elf").count() as u64;
-    // Could use Regex, but this feels less like cheating.
-    for (i, _) in phrase.match_indices("shelf") {
-        let elf_substr_start = i.saturating_sub("elf on a ".len());
-        if &phrase[elf_substr_start..i] == "elf on a " {
-            elf_counts.elf_on_shelf += 1;
-        } else {
-            elf_counts.shelf_without_elf += 1;
+// The original three phrases, as patterns for the general engine.
+const ELF_PATTERNS: &str = r#"[
+    "elf",
+    { "pattern": "shelf", "name": "elf on a shelf", "preceded_by": "elf on a " },
+    { "pattern": "shelf", "name": "shelf with no elf on it", "not_preceded_by": "elf on a " }
+]"#;
+
+// How each of the elf patterns is highlighted, in order.
+const MARKERS: [Marker; 3] = [
+    Marker { class: "elf", color: 32 },
+    Marker { class: "elf-on-a-shelf", color: 31 },
+    Marker { class: "shelf-with-no-elf", color: 33 },
+];
+
+/// Listing matches keeps every one of them, and highlighting needs the whole text, so both are
+/// only offered for bodies up to this size.
+const MATCHES_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum View {
+    /// Just the counts, as they have always been returned.
+    #[default]
+    Counts,
+    /// The counts, and every match with its offsets.
+    Matches,
+    /// As `matches`, plus the text with its matches highlighted.
+    Html,
+    Ansi,
+}
+
+#[derive(Deserialize)]
+struct ElfParams {
+    #[serde(default)]
+    view: View,
+}
+
+#[derive(Serialize)]
+struct ElfMatch {
+    category: String,
+    bytes: Range<usize>,
+    chars: Range<usize>,
+}
+
+#[derive(Serialize)]
+struct ElfReport {
+    counts: ElfCounts,
+    /// Ordered by where they start, outer matches first.
+    matches: Vec<ElfMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlighted: Option<String>,
+}
+
+// Counts chunk by chunk, so arbitrarily large documents are never held in memory, unless
+// their matches are listed or highlighted.
+async fn count_elves(
+    Query(params): Query<ElfParams>,
+    mut body: BodyStream,
+) -> Result<Response, AppError> {
+    let specs = serde_json::from_str(ELF_PATTERNS).expect("Elf patterns should be valid.");
+    let matcher = PhraseMatcher::new(specs, MatchOptions::default())
+        .expect("Elf patterns should compile.");
+    let style = match params.view {
+        View::Html => Some(Style::Html),
+        View::Ansi => Some(Style::Ansi),
+        _ => None,
+    };
+
+    let mut counts = [0u64; 3];
+    let mut matches = Vec::new();
+    let mut on_match = |m: Match| {
+        counts[m.spec] += 1;
+        if params.view != View::Counts {
+            matches.push(m);
         }
+    };
+    let mut text = Vec::new();
+    let mut read = 0;
+    let mut stream = matcher.stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| AppError::BadRequest(format!("Unable to read body: {}", e)))?;
+        read += chunk.len();
+        if params.view != View::Counts && read > MATCHES_BODY_LIMIT {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Listing or highlighting matches is limited to bodies of {} bytes", MATCHES_BODY_LIMIT
+            )));
+        }
+        stream.push(&chunk, &mut on_match)?;
+        if style.is_some() {
+            text.extend_from_slice(&chunk);
+        }
+    }
+    stream.finish(&mut on_match)?;
+
+    let counts = ElfCounts {
+        elf: counts[0],
+        elf_on_shelf: counts[1],
+        shelf_without_elf: counts[2],
+    };
+    if params.view == View::Counts {
+        return Ok(Json(counts).into_response());
     }
-    Json(elf_counts)
+
+    let highlighted = style.map(|style| {
+        let text = std::str::from_utf8(&text).expect("Matched text should be valid UTF-8.");
+        highlight::render(text, &matches, &MARKERS, style)
+    });
+    matches.sort_by(|a, b| a.bytes.start.cmp(&b.bytes.start).then(b.bytes.end.cmp(&a.bytes.end)));
+    let matches = matches.into_iter()
+        .map(|m| ElfMatch { category: matcher.specs()[m.spec].name.clone(), bytes: m.bytes, chars: m.chars })
+        .collect();
+    Ok(Json(ElfReport { counts, matches, highlighted }).into_response())
+}
+
+#[derive(Deserialize)]
+struct CountRequest {
+    text: String,
+    patterns: Vec<PatternSpec>,
+    #[serde(flatten)]
+    options: MatchOptions,
+}
+
+// Count every pattern in one pass over the text, keyed by pattern name.
+async fn count_phrases(Json(request): Json<CountRequest>) -> Result<Json<BTreeMap<String, u64>>, AppError> {
+    let matcher = PhraseMatcher::new(request.patterns, request.options)?;
+    let counts = matcher.count(&request.text);
+    Ok(Json(matcher.specs().iter()
+        .map(|spec| spec.name.clone())
+        .zip(counts)
+        .collect()))
 }
 
+/// Bodies may be gzip-compressed, with `Content-Encoding: gzip`.
 pub fn elf_router() -> Router {
-    Router::new().route("/", post(count_elves))
+    // Decompression only fails as the body is read, so its service never errors.
+    let decompression = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(|e: BoxError| async move { AppError::Internal(e.to_string()) }))
+        .layer(RequestDecompressionLayer::new());
+    Router::<(), DecompressionBody<Body>>::new()
+        .route("/", post(count_elves))
+        .route("/count", post(count_phrases))
+        .layer(decompression)
 }
\ No newline at end of file
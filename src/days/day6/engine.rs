@@ -0,0 +1,310 @@
+//! Multi-pattern phrase counting, in a single Aho-Corasick pass over the text.
+use std::{collections::HashSet, ops::Range};
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use serde::Deserialize;
+
+use crate::error::AppError;
+
+pub const MAX_PATTERNS: usize = 1000;
+const MAX_PATTERN_LENGTH: usize = 1024;
+
+/// A phrase to count, optionally only where some context does or does not come before it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "PatternSpecRepr")]
+pub struct PatternSpec {
+    pub pattern: String,
+    /// Reported name, which defaults to the pattern.
+    pub name: String,
+    pub preceded_by: Option<String>,
+    pub not_preceded_by: Option<String>,
+}
+
+/// A match of one spec, by its index, with byte and character offsets into the whole text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub spec: usize,
+    pub bytes: Range<usize>,
+    pub chars: Range<usize>,
+}
+
+// A bare string is a pattern with no context rules.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternSpecRepr {
+    Pattern(String),
+    Spec {
+        pattern: String,
+        name: Option<String>,
+        preceded_by: Option<String>,
+        not_preceded_by: Option<String>,
+    },
+}
+
+impl From<PatternSpecRepr> for PatternSpec {
+    fn from(repr: PatternSpecRepr) -> Self {
+        match repr {
+            PatternSpecRepr::Pattern(pattern) => PatternSpec {
+                name: pattern.clone(),
+                pattern,
+                preceded_by: None,
+                not_preceded_by: None,
+            },
+            PatternSpecRepr::Spec { pattern, name, preceded_by, not_preceded_by } => PatternSpec {
+                name: name.unwrap_or_else(|| pattern.clone()),
+                pattern,
+                preceded_by,
+                not_preceded_by,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MatchOptions {
+    /// Count every occurrence of a pattern, even where it overlaps an earlier one.
+    pub overlapping: bool,
+    /// Ignore ASCII case, in patterns and their context.
+    pub case_insensitive: bool,
+    /// Only count matches that are not part of a longer word.
+    pub whole_word: bool,
+}
+
+/// Compiled patterns, ready to scan any number of texts.
+pub struct PhraseMatcher {
+    specs: Vec<PatternSpec>,
+    options: MatchOptions,
+    automaton: AhoCorasick,
+    // Indices into `specs`, for each distinct pattern in the automaton.
+    specs_by_pattern: Vec<Vec<usize>>,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl PhraseMatcher {
+    pub fn new(specs: Vec<PatternSpec>, options: MatchOptions) -> Result<Self, AppError> {
+        if specs.is_empty() || specs.len() > MAX_PATTERNS {
+            return Err(AppError::BadRequest(format!("Expected 1-{} patterns", MAX_PATTERNS)));
+        }
+        let mut names = HashSet::new();
+        for spec in &specs {
+            if spec.pattern.is_empty() || spec.pattern.len() > MAX_PATTERN_LENGTH {
+                return Err(AppError::BadRequest(
+                    format!("Patterns must be 1-{} bytes long", MAX_PATTERN_LENGTH)
+                ));
+            }
+            if !names.insert(spec.name.as_str()) {
+                return Err(AppError::BadRequest(format!("Duplicate pattern name: {}", spec.name)));
+            }
+        }
+
+        // Several specs may share a pattern, with different context rules.
+        let mut patterns: Vec<&str> = Vec::new();
+        let mut specs_by_pattern: Vec<Vec<usize>> = Vec::new();
+        for (i, spec) in specs.iter().enumerate() {
+            let existing = patterns.iter().position(|p| {
+                if options.case_insensitive { p.eq_ignore_ascii_case(&spec.pattern) } else { *p == spec.pattern }
+            });
+            match existing {
+                Some(p) => specs_by_pattern[p].push(i),
+                None => {
+                    patterns.push(&spec.pattern);
+                    specs_by_pattern.push(vec![i]);
+                },
+            }
+        }
+
+        // Overlapping search needs the standard match semantics.
+        let automaton = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::Standard)
+            .ascii_case_insensitive(options.case_insensitive)
+            .build(&patterns)
+            .map_err(|e| AppError::BadRequest(format!("Unable to compile patterns: {}", e)))?;
+
+        Ok(Self { specs, options, automaton, specs_by_pattern })
+    }
+
+    pub fn specs(&self) -> &[PatternSpec] {
+        &self.specs
+    }
+
+    fn ends_with(&self, text: &str, suffix: &str) -> bool {
+        if self.options.case_insensitive {
+            text.len() >= suffix.len()
+                && text.as_bytes()[text.len() - suffix.len()..].eq_ignore_ascii_case(suffix.as_bytes())
+        } else {
+            text.ends_with(suffix)
+        }
+    }
+
+    fn is_whole_word(text: &str, range: &Range<usize>) -> bool {
+        let before = text[..range.start].chars().next_back();
+        let after = text[range.end..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    }
+
+    fn context_matches(&self, spec: &PatternSpec, before: &str) -> bool {
+        spec.preceded_by.as_ref().is_none_or(|p| self.ends_with(before, p))
+            && spec.not_preceded_by.as_ref().is_none_or(|p| !self.ends_with(before, p))
+    }
+
+    // Bytes of earlier text a match ending in new text could need: its own start, its
+    // context, and one character before it for the whole-word check.
+    fn lookbehind(&self) -> usize {
+        let context = self.specs.iter()
+            .flat_map(|s| [&s.preceded_by, &s.not_preceded_by])
+            .flatten()
+            .map(String::len)
+            .max()
+            .unwrap_or(0);
+        let pattern = self.specs.iter().map(|s| s.pattern.len()).max().unwrap_or(0);
+        pattern + context + 4
+    }
+
+    // Report the matches in `window` that end after `scan.reported` and no later than `limit`,
+    // all in absolute offsets. The overlapping search yields matches in order of their end.
+    fn scan(&self, scan: &mut ScanState, window: &str, limit: usize, f: &mut impl FnMut(Match)) {
+        for m in self.automaton.find_overlapping_iter(window) {
+            let range = scan.base + m.start()..scan.base + m.end();
+            if range.end <= scan.reported {
+                continue;
+            }
+            if range.end > limit {
+                break;
+            }
+            if self.options.whole_word && !Self::is_whole_word(window, &m.range()) {
+                continue;
+            }
+            // Without overlapping, a pattern's next match must start after its previous one ends.
+            let pattern = m.pattern().as_usize();
+            if !self.options.overlapping && range.start < scan.last_end[pattern] {
+                continue;
+            }
+            scan.last_end[pattern] = range.end;
+
+            let mut chars = None;
+            for &i in &self.specs_by_pattern[pattern] {
+                if self.context_matches(&self.specs[i], &window[..m.start()]) {
+                    let chars = chars.get_or_insert_with(|| {
+                        let end = scan.advance(window, range.end);
+                        end - window[m.range()].chars().count()..end
+                    });
+                    f(Match { spec: i, bytes: range.clone(), chars: chars.clone() });
+                }
+            }
+        }
+        scan.advance(window, limit);
+        scan.reported = limit;
+    }
+
+    /// Every match in `text`, ordered by end.
+    pub fn find_all(&self, text: &str) -> Vec<Match> {
+        let mut matches = Vec::new();
+        let mut scan = ScanState::new(self.specs_by_pattern.len());
+        self.scan(&mut scan, text, text.len(), &mut |m| matches.push(m));
+        matches
+    }
+
+    /// Number of matches for each spec, in the order they were given.
+    pub fn count(&self, text: &str) -> Vec<u64> {
+        let mut counts = vec![0; self.specs.len()];
+        for m in self.find_all(text) {
+            counts[m.spec] += 1;
+        }
+        counts
+    }
+
+    /// Match a text that arrives in chunks, without holding all of it.
+    pub fn stream(&self) -> MatchStream<'_> {
+        MatchStream {
+            matcher: self,
+            scan: ScanState::new(self.specs_by_pattern.len()),
+            window: String::new(),
+            partial: Vec::new(),
+            lookbehind: self.lookbehind(),
+        }
+    }
+}
+
+struct ScanState {
+    // Absolute offset of the start of the current window.
+    base: usize,
+    // Matches ending at or before this absolute offset have been reported.
+    reported: usize,
+    // Absolute end of the last match of each pattern.
+    last_end: Vec<usize>,
+    // Characters before the absolute byte offset `cursor`, which only moves forward.
+    cursor: usize,
+    cursor_chars: usize,
+}
+
+impl ScanState {
+    fn new(patterns: usize) -> Self {
+        Self { base: 0, reported: 0, last_end: vec![0; patterns], cursor: 0, cursor_chars: 0 }
+    }
+
+    // Move the cursor to the absolute byte offset `to`, returning the characters before it.
+    fn advance(&mut self, window: &str, to: usize) -> usize {
+        if to > self.cursor {
+            self.cursor_chars += window[self.cursor - self.base..to - self.base].chars().count();
+            self.cursor = to;
+        }
+        self.cursor_chars
+    }
+}
+
+/// Incremental matching over chunks, keeping only enough of the earlier text to find
+/// matches and context that span chunk boundaries. Ranges are offsets into the whole text.
+pub struct MatchStream<'m> {
+    matcher: &'m PhraseMatcher,
+    scan: ScanState,
+    window: String,
+    // Bytes of a UTF-8 character split across chunks.
+    partial: Vec<u8>,
+    lookbehind: usize,
+}
+
+impl MatchStream<'_> {
+    pub fn push(&mut self, chunk: &[u8], mut f: impl FnMut(Match)) -> Result<(), AppError> {
+        self.partial.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(AppError::BadRequest(format!(
+                "Invalid UTF-8 at byte {}", self.scan.base + self.window.len() + e.valid_up_to()
+            ))),
+        };
+        let text = std::str::from_utf8(&self.partial[..valid]).expect("Prefix should be valid UTF-8.");
+        self.window.push_str(text);
+        self.partial.drain(..valid);
+
+        // A whole-word match needs the character after it, which may be in the next chunk.
+        let mut limit = self.window.len();
+        if self.matcher.options.whole_word {
+            limit = self.window.char_indices().next_back().map_or(0, |(i, _)| i);
+        }
+        let absolute_limit = self.scan.base + limit;
+        self.matcher.scan(&mut self.scan, &self.window, absolute_limit, &mut f);
+
+        // Keep enough of the window to see matches that end in the next chunk.
+        let mut cut = limit.saturating_sub(self.lookbehind);
+        while !self.window.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        self.window.drain(..cut);
+        self.scan.base += cut;
+        Ok(())
+    }
+
+    pub fn finish(mut self, mut f: impl FnMut(Match)) -> Result<(), AppError> {
+        if !self.partial.is_empty() {
+            return Err(AppError::BadRequest("Text ends with an incomplete UTF-8 character".into()));
+        }
+        let limit = self.scan.base + self.window.len();
+        self.matcher.scan(&mut self.scan, &self.window, limit, &mut f);
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+//! Rendering of a text with its matches highlighted, as HTML or for a terminal.
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use super::engine::Match;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    /// Escaped text, with each match in a `<mark>` classed by its category.
+    Html,
+    /// Each match in the ANSI color of its category.
+    Ansi,
+}
+
+/// How to mark the matches of one spec: an HTML class and an ANSI SGR color code.
+pub struct Marker {
+    pub class: &'static str,
+    pub color: u8,
+}
+
+// Control characters in the text could move the cursor or restyle the viewer's terminal, so all
+// but tabs and newlines are shown as their Unicode control pictures, or as U+FFFD past DEL.
+fn push_terminal_text(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '\t' | '\n' => out.push(c),
+            '\0'..='\x1f' => out.push(char::from_u32(0x2400 + c as u32).expect("Control pictures are chars.")),
+            '\x7f' => out.push('\u{2421}'),
+            '\u{80}'..='\u{9f}' => out.push(char::REPLACEMENT_CHARACTER),
+            c => out.push(c),
+        }
+    }
+}
+
+fn push_text(out: &mut String, text: &str, style: Style) {
+    if style == Style::Ansi {
+        push_terminal_text(out, text);
+        return;
+    }
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn open(out: &mut String, marker: &Marker, style: Style) {
+    match style {
+        Style::Html => write!(out, r#"<mark class="{}">"#, marker.class),
+        Style::Ansi => write!(out, "\x1b[{}m", marker.color),
+    }.expect("Writing to a String should not fail.");
+}
+
+// ANSI colors do not nest, so closing an inner match restores the outer one's color.
+fn close(out: &mut String, outer: Option<&Marker>, style: Style) {
+    match style {
+        Style::Html => out.push_str("</mark>"),
+        Style::Ansi => {
+            out.push_str("\x1b[0m");
+            if let Some(marker) = outer {
+                open(out, marker, style);
+            }
+        },
+    }
+}
+
+/// Highlight `matches` in `text`, using the marker for each match's spec.
+///
+/// Matches must nest: one that starts inside another ends inside it too.
+pub fn render(text: &str, matches: &[Match], markers: &[Marker], style: Style) -> String {
+    let mut sorted: Vec<&Match> = matches.iter().collect();
+    sorted.sort_by(|a, b| a.bytes.start.cmp(&b.bytes.start).then(b.bytes.end.cmp(&a.bytes.end)));
+
+    let mut out = String::with_capacity(text.len());
+    let mut open_matches: Vec<&Match> = Vec::new();
+    let mut position = 0;
+    let close_until = |out: &mut String, open_matches: &mut Vec<&Match>, position: &mut usize, offset: usize| {
+        while let Some(m) = open_matches.last().filter(|m| m.bytes.end <= offset) {
+            push_text(out, &text[*position..m.bytes.end], style);
+            *position = m.bytes.end;
+            open_matches.pop();
+            let outer = open_matches.last().filter(|outer| outer.bytes.end > *position);
+            close(out, outer.map(|m| &markers[m.spec]), style);
+        }
+    };
+
+    for m in sorted {
+        close_until(&mut out, &mut open_matches, &mut position, m.bytes.start);
+        debug_assert!(open_matches.last().is_none_or(|outer| m.bytes.end <= outer.bytes.end));
+        push_text(&mut out, &text[position..m.bytes.start], style);
+        position = m.bytes.start;
+        open(&mut out, &markers[m.spec], style);
+        open_matches.push(m);
+    }
+    close_until(&mut out, &mut open_matches, &mut position, text.len());
+    push_text(&mut out, &text[position..], style);
+    out
+}
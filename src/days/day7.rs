@@ -1,77 +1,256 @@
-use std::collections::HashMap;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
 use axum::{
-    extract::Json,
-    http::{
-        header::HeaderMap,
-        StatusCode
-    },
-    routing::get,
+    extract::State,
+    http::{header::HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Router,
 };
-use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::cookie::{Cookie, CookieJar, Key, PrivateCookieJar, SignedCookieJar};
 use base64::{Engine, prelude::BASE64_STANDARD};
-use serde::{ Serialize, Deserialize };
+use serde::{de::IgnoredAny, Serialize, Deserialize};
 use serde_json::Value;
 
+use crate::{error::AppError, extract::{Json, Query}};
 
-#[derive(Deserialize)]
+mod planner;
+mod units;
+use planner::{Plan, PlanRequest};
+use units::Quantity;
+
+#[derive(Deserialize, Serialize)]
 struct RecipeInput {
-    recipe: HashMap<String, u64>,
-    pantry: HashMap<String, u64>
+    recipe: HashMap<String, Quantity>,
+    pantry: HashMap<String, Quantity>
 }
 
 #[derive(Serialize)]
 struct RecipeOutput {
     cookies: u64,
-    pantry: HashMap<String, u64>
+    /// In the units the pantry was given in.
+    pantry: HashMap<String, Quantity>
+}
+
+const RECIPE_COOKIE: &str = "recipe";
+
+pub struct RecipeCookieConfig {
+    /// Signs and encrypts issued cookies. At least 64 bytes.
+    pub key: Key,
+    /// Also accept plain base64 cookies, which anyone can forge.
+    pub accept_plain: bool,
+}
+
+impl Default for RecipeCookieConfig {
+    /// A random key, so cookies only verify until a restart, and plain cookies accepted.
+    fn default() -> Self {
+        Self { key: Key::generate(), accept_plain: true }
+    }
+}
+
+impl RecipeCookieConfig {
+    /// Configuration from a base64 key and a mode, `compat` or `strict`, as given to the server.
+    pub fn from_settings(key: Option<&str>, mode: Option<&str>) -> Result<Self, String> {
+        let key = match key {
+            Some(encoded) => {
+                let bytes = BASE64_STANDARD.decode(encoded.trim())
+                    .map_err(|e| format!("Cookie key is not valid base64: {}", e))?;
+                Key::try_from(bytes.as_slice())
+                    .map_err(|_| "Cookie key must be at least 64 bytes".to_string())?
+            },
+            None => Key::generate(),
+        };
+        let accept_plain = match mode {
+            None | Some("compat") => true,
+            Some("strict") => false,
+            Some(other) => return Err(format!("Unknown cookie mode: {}", other)),
+        };
+        Ok(Self { key, accept_plain })
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CookieKind {
+    /// Plain base64, only accepted in compat mode.
+    Plain,
+    /// Readable by the client, but any change is detected.
+    #[default]
+    Signed,
+    /// Encrypted, so the client can neither read nor change it.
+    Private,
+}
+
+// Whether a cookie value is a plain one: base64-encoded JSON.
+fn is_plain(value: &str) -> bool {
+    BASE64_STANDARD.decode(value)
+        .is_ok_and(|json| serde_json::from_slice::<IgnoredAny>(&json).is_ok())
+}
+
+// The cookie holds base64-encoded JSON, which a signed or private cookie wraps in turn.
+// Returns the JSON and how it was wrapped.
+fn decode_recipe_cookie(
+    config: &RecipeCookieConfig,
+    headers: &HeaderMap,
+) -> Result<(Vec<u8>, CookieKind), AppError> {
+    let cookie = CookieJar::from_headers(headers)
+        .get(RECIPE_COOKIE)
+        .cloned()
+        .ok_or_else(|| AppError::BadRequest("Could not find recipe cookie in header".into()))?;
+
+    let verified = PrivateCookieJar::from_headers(headers, config.key.clone()).get(RECIPE_COOKIE)
+        .map(|c| (c, CookieKind::Private))
+        .or_else(|| SignedCookieJar::from_headers(headers, config.key.clone()).get(RECIPE_COOKIE)
+            .map(|c| (c, CookieKind::Signed)));
+    let (value, kind) = match verified {
+        Some((verified, kind)) => (verified.value().to_string(), kind),
+        // Anything else is taken to be a signed or private cookie that failed verification.
+        None if config.accept_plain && is_plain(cookie.value()) =>
+            (cookie.value().to_string(), CookieKind::Plain),
+        None => return Err(AppError::InvalidSignature(
+            "Recipe cookie was not issued by this server, or has been tampered with".into()
+        )),
+    };
+
+    let payload = BASE64_STANDARD.decode(value)
+        .map_err(|e| AppError::BadRequest(format!("Unable to decode string: {}", e)))?;
+    Ok((payload, kind))
+}
+
+// Add a `recipe` cookie holding `payload` to `response`, wrapped as `kind`.
+fn with_recipe_cookie(
+    config: &RecipeCookieConfig,
+    kind: CookieKind,
+    payload: &impl Serialize,
+    response: impl IntoResponse,
+) -> Response {
+    let json = serde_json::to_string(payload).expect("Recipes should serialize to JSON.");
+    let cookie = Cookie::build(RECIPE_COOKIE, BASE64_STANDARD.encode(json))
+        .path("/")
+        .http_only(true)
+        .finish();
+    let key = config.key.clone();
+    match kind {
+        CookieKind::Plain => (CookieJar::new().add(cookie), response).into_response(),
+        CookieKind::Signed => (SignedCookieJar::new(key).add(cookie), response).into_response(),
+        CookieKind::Private => (PrivateCookieJar::new(key).add(cookie), response).into_response(),
+    }
+}
+
+fn parse_recipe_input(payload: &[u8]) -> Result<RecipeInput, AppError> {
+    serde_json::from_slice(payload)
+        .map_err(|e| AppError::BadRequest(format!("Unable to convert to json: {}", e)))
 }
 
-fn decode_recipe_cookie(headers: &HeaderMap) -> Result<Vec<u8>, (StatusCode, String)> {
-    let cookie_val = headers.get_all("cookie")
-        .iter()
-        .filter_map(|v| match Cookie::parse(v.to_str().unwrap()) {
-            Ok(cookie) => if cookie.name() == "recipe" { Some(Ok(cookie)) } else { None },
-            Err(e) => Some(Err(e))
-        }).next().ok_or((StatusCode::BAD_REQUEST, "Could not find cookies in header".into()))?;
-    
-    let cookie = cookie_val.map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to parse cookie: {}", e)))?;
+#[derive(Deserialize)]
+struct IssueParams {
+    #[serde(default)]
+    kind: CookieKind,
+}
 
-    BASE64_STANDARD.decode(cookie.value())
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to decode string: {}", e)))
+// Set a recipe cookie that `/decode` and `/bake` can verify.
+async fn issue(
+    State(config): State<Arc<RecipeCookieConfig>>,
+    Query(params): Query<IssueParams>,
+    Json(recipe): Json<Value>,
+) -> Result<Response, AppError> {
+    if matches!(params.kind, CookieKind::Plain) && !config.accept_plain {
+        return Err(AppError::BadRequest("Plain cookies are not accepted by this server".into()));
+    }
+    Ok(with_recipe_cookie(&config, params.kind, &recipe, StatusCode::NO_CONTENT))
 }
 
-async fn decode(headers: HeaderMap) -> Result<Json<Value>, (StatusCode, String)> {
-    let encoded_cookie = decode_recipe_cookie(&headers)?;
+async fn decode(
+    State(config): State<Arc<RecipeCookieConfig>>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    let (encoded_cookie, _) = decode_recipe_cookie(&config, &headers)?;
     let decoded_recipe = serde_json::from_slice(&encoded_cookie)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to convert to json: {}", e)))?;
+        .map_err(|e| AppError::BadRequest(format!("Unable to convert to json: {}", e)))?;
     Ok(Json(decoded_recipe))
 }
 
-async fn bake(headers: HeaderMap) -> Result<Json<RecipeOutput>, (StatusCode, String)> {
-    let encoded_cookie = decode_recipe_cookie(&headers)?;
-    let decoded_input: RecipeInput = serde_json::from_slice(&encoded_cookie)
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Unable to convert to json: {}", e)))?;
+#[derive(Deserialize)]
+struct BakeParams {
+    /// Also set the cookie to the recipe with the remaining pantry, ready to bake again.
+    #[serde(default)]
+    set_cookie: bool,
+}
+
+async fn bake(
+    State(config): State<Arc<RecipeCookieConfig>>,
+    Query(params): Query<BakeParams>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (encoded_cookie, kind) = decode_recipe_cookie(&config, &headers)?;
+    let decoded_input = parse_recipe_input(&encoded_cookie)?;
 
-    let cookies = decoded_input.recipe.iter()
-        .filter(|(_, &count)| count > 0)
-        .filter_map(|(ingredient, &count)| match decoded_input.pantry.get(ingredient) {
-            Some(p) => p.checked_div(count), // Ignore if ingredient has 0 count.
-            None => Some(0) // Can't make cookies without ingredient
-        }).min().ok_or((StatusCode::BAD_REQUEST, "Could not find all ingredients in pantry".into()))?;
+    // Ingredients needed in no amount are ignored.
+    let needed = || decoded_input.recipe.iter().filter(|(_, needed)| needed.amount > 0.0);
+    let cookies = needed()
+        .map(|(ingredient, needed)| match decoded_input.pantry.get(ingredient) {
+            Some(stock) => stock.batches(needed, ingredient),
+            None => Ok(0) // Can't make cookies without ingredient
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .min()
+        .ok_or_else(|| AppError::BadRequest("Could not find all ingredients in pantry".into()))?;
 
     let mut pantry = decoded_input.pantry.clone();
     if cookies > 0 {
-        for (ingredient, &count) in decoded_input.recipe.iter() {
-            if let Some(pantry_entry) = pantry.get_mut(ingredient) {
-                *pantry_entry -= cookies * count;
+        for (ingredient, needed) in needed() {
+            if let Some(stock) = pantry.get_mut(ingredient) {
+                stock.take(needed, cookies, ingredient)?;
             }
         }
     }
+    let recipe = decoded_input.recipe;
+
+    if !params.set_cookie {
+        return Ok(Json(RecipeOutput { cookies, pantry }).into_response());
+    }
+    let next = RecipeInput { recipe, pantry: pantry.clone() };
+    Ok(with_recipe_cookie(&config, kind, &next, Json(RecipeOutput { cookies, pantry })))
+}
+
+// Add each amount to the pantry in the cookie, converting to the units already stocked and
+// stocking new ingredients as given, and set the cookie to the result.
+async fn restock(
+    State(config): State<Arc<RecipeCookieConfig>>,
+    headers: HeaderMap,
+    Json(amounts): Json<HashMap<String, Quantity>>,
+) -> Result<Response, AppError> {
+    let (encoded_cookie, kind) = decode_recipe_cookie(&config, &headers)?;
+    let mut input = parse_recipe_input(&encoded_cookie)?;
+    for (ingredient, amount) in amounts {
+        match input.pantry.entry(ingredient) {
+            Entry::Occupied(mut stock) => {
+                let ingredient = stock.key().clone();
+                stock.get_mut().add(&amount, &ingredient)?;
+            },
+            Entry::Vacant(entry) => { entry.insert(amount); },
+        }
+    }
+    Ok(with_recipe_cookie(&config, kind, &input, Json(&input)))
+}
 
-    Ok(Json(RecipeOutput { cookies, pantry }))
+// Choose how many of several recipes to bake from one pantry, for the most total value.
+// The search can take a while, so it runs off the async executor.
+async fn plan(Json(request): Json<PlanRequest>) -> Result<Json<Plan>, AppError> {
+    let plan = tokio::task::spawn_blocking(move || planner::plan(request))
+        .await
+        .map_err(|e| AppError::Internal(format!("Internal error: {}", e)))??;
+    Ok(Json(plan))
 }
 
-pub fn cookie_router() -> Router {
+pub fn cookie_router(config: RecipeCookieConfig) -> Router {
     Router::new().route("/decode", get(decode))
         .route("/bake", get(bake))
+        .route("/issue", post(issue))
+        .route("/pantry", post(restock))
+        .route("/plan", post(plan))
+        .with_state(Arc::new(config))
 }
\ No newline at end of file
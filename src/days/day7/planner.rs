@@ -0,0 +1,239 @@
+//! Planning how many of several recipes to bake from one pantry, for the most total value.
+//!
+//! This is a small integer program, solved exactly by branch and bound. Each node's bound is
+//! the best value any single ingredient allows when recipes may be baked fractionally.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use super::units::{fitting_batches, Quantity};
+use crate::error::AppError;
+
+pub const MAX_RECIPES: usize = 32;
+/// Distinct ingredients, in the pantry and the recipes together.
+pub const MAX_INGREDIENTS: usize = 64;
+/// Batch counts tried before giving up on proving the plan optimal.
+const MAX_SEARCH_STEPS: u64 = 1_000_000;
+/// Recipe and ingredient pairs bounded, over all the batch counts tried. Each try bounds every pair,
+/// so larger requests get fewer tries, and no request takes more than about a second.
+const MAX_SEARCH_WORK: u64 = 50_000_000;
+// Relative slack for floating-point comparisons of amounts and values.
+const EPSILON: f64 = 1e-9;
+
+fn default_value() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+pub struct PlanRecipe {
+    pub ingredients: HashMap<String, Quantity>,
+    /// Value of one batch.
+    #[serde(default = "default_value")]
+    pub value: f64,
+    /// Among plans of equal value, those baking more of higher-priority recipes win.
+    #[serde(default)]
+    pub priority: i64,
+}
+
+#[derive(Deserialize)]
+pub struct PlanRequest {
+    pub recipes: HashMap<String, PlanRecipe>,
+    pub pantry: HashMap<String, Quantity>,
+}
+
+#[derive(Serialize)]
+pub struct Plan {
+    /// Batches of each recipe.
+    pub plan: BTreeMap<String, u64>,
+    pub value: f64,
+    /// Whether the search finished, rather than stopping at its step limit.
+    pub optimal: bool,
+    /// What is left, in the units the pantry was given in.
+    pub pantry: HashMap<String, Quantity>,
+    /// Ingredients that prevent baking one more batch of some recipe.
+    pub limiting_ingredients: Vec<String>,
+}
+
+struct Search<'a> {
+    // Recipes in branching order, by priority and then value.
+    values: Vec<f64>,
+    // Amount of each ingredient per batch of each recipe, in pantry units.
+    needs: Vec<Vec<f64>>,
+    // For each ingredient, the recipes using it by value per amount, best first.
+    by_density: Vec<Vec<usize>>,
+    remaining: &'a mut [f64],
+    counts: Vec<u64>,
+    best: Option<(f64, Vec<u64>)>,
+    steps: u64,
+    // Batch counts to try, fewer for larger requests.
+    max_steps: u64,
+    // Whether the search stopped at its step limit, before proving the best plan optimal.
+    truncated: bool,
+}
+
+impl Search<'_> {
+    fn max_batches(&self, recipe: usize) -> u64 {
+        self.needs[recipe].iter()
+            .zip(self.remaining.iter())
+            .filter(|(&need, _)| need > 0.0)
+            .map(|(&need, &left)| fitting_batches(left, need) as u64)
+            .min()
+            .expect("Every recipe should need some ingredient.")
+    }
+
+    // The most value recipes `from..` could add, if they could be baked fractionally and
+    // had to share only one ingredient, for the tightest such ingredient.
+    fn bound(&self, from: usize) -> f64 {
+        let limits: Vec<u64> = (0..self.values.len())
+            .map(|r| if r >= from { self.max_batches(r) } else { 0 })
+            .collect();
+        (0..self.remaining.len())
+            .map(|j| {
+                let mut left = self.remaining[j];
+                let mut value: f64 = (from..self.values.len())
+                    .filter(|&r| self.needs[r][j] == 0.0)
+                    .map(|r| self.values[r] * limits[r] as f64)
+                    .sum();
+                for &r in self.by_density[j].iter().filter(|&&r| r >= from) {
+                    let batches = (left / self.needs[r][j]).min(limits[r] as f64);
+                    value += self.values[r] * batches;
+                    left -= self.needs[r][j] * batches;
+                }
+                value
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    // Tries batch counts from the most down, so the first plan found with the best value
+    // is the one favoring the earliest recipes.
+    fn run(&mut self, recipe: usize, value: f64) {
+        if recipe == self.values.len() {
+            if self.best.as_ref().is_none_or(|(best, _)| value > best + EPSILON * best.abs().max(1.0)) {
+                self.best = Some((value, self.counts.clone()));
+            }
+            return;
+        }
+
+        for batches in (0..=self.max_batches(recipe)).rev() {
+            if self.steps >= self.max_steps {
+                self.truncated = true;
+                return;
+            }
+            self.steps += 1;
+
+            for (left, need) in self.remaining.iter_mut().zip(&self.needs[recipe]) {
+                *left -= need * batches as f64;
+            }
+            self.counts[recipe] = batches;
+            let value = value + self.values[recipe] * batches as f64;
+            let promising = self.best.as_ref().is_none_or(|(best, _)| {
+                value + self.bound(recipe + 1) > best + EPSILON * best.abs().max(1.0)
+            });
+            if promising {
+                self.run(recipe + 1, value);
+            }
+            for (left, need) in self.remaining.iter_mut().zip(&self.needs[recipe]) {
+                *left += need * batches as f64;
+            }
+        }
+        self.counts[recipe] = 0;
+    }
+}
+
+pub fn plan(request: PlanRequest) -> Result<Plan, AppError> {
+    if request.recipes.is_empty() || request.recipes.len() > MAX_RECIPES {
+        return Err(AppError::BadRequest(format!("Expected 1-{} recipes", MAX_RECIPES)));
+    }
+    for (name, recipe) in &request.recipes {
+        if !recipe.value.is_finite() || recipe.value < 0.0 {
+            return Err(AppError::BadRequest(format!("Value of {} must not be negative", name)));
+        }
+        if recipe.ingredients.values().all(|q| q.amount == 0.0) {
+            return Err(AppError::BadRequest(format!("Recipe {} needs no ingredients", name)));
+        }
+    }
+
+    let mut names: Vec<&String> = request.recipes.keys().collect();
+    names.sort_by(|a, b| {
+        let (a_recipe, b_recipe) = (&request.recipes[*a], &request.recipes[*b]);
+        b_recipe.priority.cmp(&a_recipe.priority)
+            .then(b_recipe.value.total_cmp(&a_recipe.value))
+            .then(a.cmp(b))
+    });
+
+    // Ingredients missing from the pantry are stocked at zero, in the recipe's own units.
+    let mut pantry = request.pantry.clone();
+    for recipe in request.recipes.values() {
+        for (ingredient, needed) in recipe.ingredients.iter().filter(|(_, q)| q.amount > 0.0) {
+            pantry.entry(ingredient.clone()).or_insert(Quantity::new(0.0, needed.unit));
+        }
+    }
+    if pantry.len() > MAX_INGREDIENTS {
+        return Err(AppError::BadRequest(format!("Expected at most {} ingredients", MAX_INGREDIENTS)));
+    }
+    let ingredients: Vec<&String> = pantry.keys().collect();
+
+    let needs = names.iter()
+        .map(|name| ingredients.iter()
+            .map(|&ingredient| match request.recipes[*name].ingredients.get(ingredient) {
+                Some(needed) if needed.amount > 0.0 => needed.amount_in(&pantry[ingredient], ingredient),
+                _ => Ok(0.0),
+            })
+            .collect::<Result<Vec<f64>, AppError>>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let values: Vec<f64> = names.iter().map(|name| request.recipes[*name].value).collect();
+    let by_density = (0..ingredients.len())
+        .map(|j| {
+            let mut recipes: Vec<usize> = (0..names.len()).filter(|&r| needs[r][j] > 0.0).collect();
+            recipes.sort_by(|&a, &b| (values[b] / needs[b][j]).total_cmp(&(values[a] / needs[a][j])));
+            recipes
+        })
+        .collect();
+
+    let mut remaining: Vec<f64> = ingredients.iter().map(|&i| pantry[i].amount).collect();
+    let pairs = (names.len() * ingredients.len()).max(1) as u64;
+    let mut search = Search {
+        values,
+        needs,
+        by_density,
+        remaining: &mut remaining,
+        counts: vec![0; names.len()],
+        best: None,
+        steps: 0,
+        max_steps: (MAX_SEARCH_WORK / pairs).min(MAX_SEARCH_STEPS),
+        truncated: false,
+    };
+    search.run(0, 0.0);
+    let optimal = !search.truncated;
+    let (value, counts) = search.best.take().expect("Baking nothing should always be a plan.");
+
+    let mut left = pantry;
+    for (name, &batches) in names.iter().zip(&counts).filter(|(_, &b)| b > 0) {
+        for (ingredient, needed) in request.recipes[*name].ingredients.iter().filter(|(_, q)| q.amount > 0.0) {
+            left.get_mut(ingredient)
+                .expect("Every needed ingredient should be stocked.")
+                .take(needed, batches, ingredient)?;
+        }
+    }
+
+    let mut limiting_ingredients: Vec<String> = Vec::new();
+    for recipe in request.recipes.values() {
+        for (ingredient, needed) in recipe.ingredients.iter().filter(|(_, q)| q.amount > 0.0) {
+            if left[ingredient].batches(needed, ingredient)? == 0 && !limiting_ingredients.contains(ingredient) {
+                limiting_ingredients.push(ingredient.clone());
+            }
+        }
+    }
+    limiting_ingredients.sort();
+
+    // Ingredients the pantry did not have, and still does not, are left out.
+    left.retain(|ingredient, _| request.pantry.contains_key(ingredient));
+
+    Ok(Plan {
+        plan: names.into_iter().cloned().zip(counts).collect(),
+        value,
+        optimal,
+        pantry: left,
+        limiting_ingredients,
+    })
+}
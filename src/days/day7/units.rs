@@ -0,0 +1,224 @@
+//! Ingredient quantities, with optional units that convert within their dimension.
+use std::fmt;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::error::AppError;
+
+// Quantities are rounded to this many decimal places, hiding floating-point noise from
+// unit conversions.
+const PRECISION: f64 = 1e9;
+// Shortfall of a batch that is put down to that noise, rather than to running out.
+const TOLERANCE: f64 = 1.0 / PRECISION;
+// Largest count that an `f64` holds exactly.
+const MAX_EXACT_AMOUNT: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Unit {
+    #[serde(rename = "g")]
+    Grams,
+    #[serde(rename = "kg")]
+    Kilograms,
+    #[serde(rename = "ml")]
+    Milliliters,
+    #[serde(rename = "l")]
+    Liters,
+    /// US customary cups.
+    #[serde(rename = "cups", alias = "cup")]
+    Cups,
+    /// US customary tablespoons.
+    #[serde(rename = "tbsp")]
+    Tablespoons,
+    #[serde(rename = "pieces", alias = "piece")]
+    Pieces,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dimension {
+    Mass,
+    Volume,
+    Count,
+}
+
+impl Unit {
+    fn dimension(self) -> Dimension {
+        match self {
+            Unit::Grams | Unit::Kilograms => Dimension::Mass,
+            Unit::Milliliters | Unit::Liters | Unit::Cups | Unit::Tablespoons => Dimension::Volume,
+            Unit::Pieces => Dimension::Count,
+        }
+    }
+
+    // Size in grams, milliliters or pieces.
+    fn factor(self) -> f64 {
+        match self {
+            Unit::Grams | Unit::Milliliters | Unit::Pieces => 1.0,
+            Unit::Kilograms | Unit::Liters => 1000.0,
+            Unit::Cups => 236.5882365,
+            Unit::Tablespoons => 14.78676478125,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Unit::Grams => "g",
+            Unit::Kilograms => "kg",
+            Unit::Milliliters => "ml",
+            Unit::Liters => "l",
+            Unit::Cups => "cups",
+            Unit::Tablespoons => "tbsp",
+            Unit::Pieces => "pieces",
+        })
+    }
+}
+
+/// An amount of an ingredient. Without a unit, it is a count of pieces, and is written as a
+/// bare number rather than `{"amount": .., "unit": ..}`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "QuantityRepr")]
+pub struct Quantity {
+    pub amount: f64,
+    pub unit: Option<Unit>,
+    // The exact amount of a whole count without a unit, as `amount` rounds those above 2^53.
+    count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuantityRepr {
+    Count(u64),
+    Amount(f64),
+    Measured { amount: f64, unit: Option<Unit> },
+}
+
+impl TryFrom<QuantityRepr> for Quantity {
+    type Error = String;
+
+    fn try_from(repr: QuantityRepr) -> Result<Self, Self::Error> {
+        let (amount, unit) = match repr {
+            QuantityRepr::Count(count) => return Ok(Quantity::count(count)),
+            QuantityRepr::Amount(amount) => (amount, None),
+            QuantityRepr::Measured { amount, unit } => (amount, unit),
+        };
+        if amount < 0.0 {
+            return Err(format!("Quantity must not be negative: {}", amount));
+        }
+        Ok(Quantity::new(amount, unit))
+    }
+}
+
+// Whole amounts are written as integers, as they were before units.
+fn serialize_amount<S: Serializer>(amount: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if amount.fract() == 0.0 && *amount < u64::MAX as f64 {
+        serializer.serialize_u64(*amount as u64)
+    } else {
+        serializer.serialize_f64(*amount)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Measured {
+            #[serde(serialize_with = "serialize_amount")]
+            amount: f64,
+            unit: Unit,
+        }
+
+        match (self.unit, self.count) {
+            (None, Some(count)) => serializer.serialize_u64(count),
+            (None, None) => serialize_amount(&self.amount, serializer),
+            (Some(unit), _) => Measured { amount: self.amount, unit }.serialize(serializer),
+        }
+    }
+}
+
+fn tidy(amount: f64) -> f64 {
+    ((amount * PRECISION).round() / PRECISION).max(0.0)
+}
+
+impl Quantity {
+    /// `amount` of `unit`, or of pieces without one.
+    pub fn new(amount: f64, unit: Option<Unit>) -> Self {
+        let whole = unit.is_none() && amount.fract() == 0.0 && (0.0..=MAX_EXACT_AMOUNT).contains(&amount);
+        Self { amount, unit, count: whole.then_some(amount as u64) }
+    }
+
+    fn count(count: u64) -> Self {
+        Self { amount: count as f64, unit: None, count: Some(count) }
+    }
+
+    fn unit_name(&self) -> String {
+        self.unit.map_or_else(|| "a plain count".into(), |u| u.to_string())
+    }
+
+    fn dimension_and_factor(&self) -> (Dimension, f64) {
+        self.unit.map_or((Dimension::Count, 1.0), |u| (u.dimension(), u.factor()))
+    }
+
+    /// This amount, in the units of `other`.
+    pub fn amount_in(&self, other: &Quantity, ingredient: &str) -> Result<f64, AppError> {
+        let (dimension, factor) = self.dimension_and_factor();
+        let (other_dimension, other_factor) = other.dimension_and_factor();
+        if dimension != other_dimension {
+            return Err(AppError::UnprocessableEntity(format!(
+                "Cannot convert {} of {} to {}", self.unit_name(), ingredient, other.unit_name()
+            )));
+        }
+        Ok(self.amount * factor / other_factor)
+    }
+
+    /// How many times `needed` fits in this quantity.
+    pub fn batches(&self, needed: &Quantity, ingredient: &str) -> Result<u64, AppError> {
+        if let (Some(stock), Some(needed)) = (self.count, needed.count) {
+            return Ok(stock.checked_div(needed).unwrap_or(u64::MAX));
+        }
+        let needed = needed.amount_in(self, ingredient)?;
+        Ok(fitting_batches(self.amount, needed) as u64)
+    }
+
+    pub fn take(&mut self, needed: &Quantity, times: u64, ingredient: &str) -> Result<(), AppError> {
+        if let (Some(stock), Some(needed)) = (self.count, needed.count) {
+            let left = needed.checked_mul(times)
+                .and_then(|taken| stock.checked_sub(taken))
+                .ok_or_else(|| AppError::UnprocessableEntity(format!(
+                    "Not enough {} for {} batches", ingredient, times
+                )))?;
+            *self = Quantity::count(left);
+            return Ok(());
+        }
+        let amount = tidy(self.amount - needed.amount_in(self, ingredient)? * times as f64);
+        *self = Quantity::new(amount, self.unit);
+        Ok(())
+    }
+
+    pub fn add(&mut self, more: &Quantity, ingredient: &str) -> Result<(), AppError> {
+        let overflowed = || AppError::BadRequest(format!("Amount of {} overflowed", ingredient));
+        if let (Some(stock), Some(more)) = (self.count, more.count) {
+            *self = Quantity::count(stock.checked_add(more).ok_or_else(overflowed)?);
+            return Ok(());
+        }
+        let amount = tidy(self.amount + more.amount_in(self, ingredient)?);
+        if !amount.is_finite() {
+            return Err(overflowed());
+        }
+        *self = Quantity::new(amount, self.unit);
+        Ok(())
+    }
+}
+
+/// Whole batches of `needed` in `stock`. Conversions are not exact, so a batch still fits
+/// when it is short by no more than `TOLERANCE`, and 1 l holds exactly 4 quarter litres.
+pub fn fitting_batches(stock: f64, needed: f64) -> f64 {
+    let fits = |batches: f64| stock - batches * needed >= -TOLERANCE;
+    let batches = (stock / needed).floor();
+    if !fits(batches) {
+        batches - 1.0
+    } else if fits(batches + 1.0) {
+        batches + 1.0
+    } else {
+        batches
+    }
+}
@@ -1,49 +1,485 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
 use axum::{
-    extract::Path,
-    http::StatusCode,
-    routing::get,
+    extract::State,
+    http::{header::ACCEPT, HeaderMap},
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Router,
 };
-use serde::Deserialize;
+use futures::{stream, StreamExt};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
 
-#[derive(Deserialize)]
-struct PokemonStat {
-    weight: u64 // in hectograms.
+use crate::{error::AppError, extract::{Json, Path, Query}};
+
+/// Base URL of the public PokeAPI instance.
+pub const POKEAPI_BASE_URL: &str = "https://pokeapi.co";
+
+#[derive(Clone, Deserialize)]
+pub struct PokemonStat {
+    pub id: u64,
+    pub name: String,
+    pub weight: u64, // in hectograms.
+    pub height: u64, // in decimetres.
+}
+
+/// A Pokémon, by PokeAPI id or name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PokemonKey {
+    Id(u64),
+    Name(String),
+}
+
+impl PokemonKey {
+    /// Lowercase names, and turn numeric names into ids, so each Pokémon has one key.
+    pub fn normalize(&self) -> Result<PokemonKey, AppError> {
+        match self {
+            PokemonKey::Id(id) => Ok(PokemonKey::Id(*id)),
+            PokemonKey::Name(name) => {
+                if let Ok(id) = name.parse::<u64>() {
+                    return Ok(PokemonKey::Id(id));
+                }
+                let name = name.to_ascii_lowercase();
+                // PokeAPI names are kebab-case, and end up in the request path.
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    return Err(AppError::BadRequest(format!("Invalid Pokémon name: {:?}", name)));
+                }
+                Ok(PokemonKey::Name(name))
+            },
+        }
+    }
+}
+
+impl fmt::Display for PokemonKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PokemonKey::Id(id) => write!(f, "{}", id),
+            PokemonKey::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Somewhere to look up Pokémon stats by id or name.
+#[async_trait]
+pub trait PokemonSource: Send + Sync {
+    async fn get_pokemon_stats(&self, key: &PokemonKey) -> Result<PokemonStat, AppError>;
+}
+
+pub struct PokeApiConfig {
+    /// PokeAPI server to query, without a trailing slash.
+    pub base_url: String,
+    pub timeout: Duration,
+    /// Maximum number of Pokémon kept in the cache.
+    pub cache_capacity: NonZeroUsize,
+    /// How long a cached Pokémon is served before it is fetched again.
+    pub cache_ttl: Duration,
 }
 
-async fn get_pokemon_stats(poke_id: u64) -> reqwest::Result<PokemonStat> {
-    let poke_url = format!("https://pokeapi.co/api/v2/pokemon/{}/", poke_id);
-    reqwest::get(poke_url)
-        .await?
-        .json::<PokemonStat>()
-        .await
+impl Default for PokeApiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: POKEAPI_BASE_URL.into(),
+            timeout: Duration::from_secs(10),
+            cache_capacity: NonZeroUsize::new(1024).unwrap(),
+            cache_ttl: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// PokeAPI client, with a shared connection pool and an LRU cache of stats.
+///
+/// Concurrent misses for the same key share a single upstream fetch.
+pub struct PokeApi {
+    client: reqwest::Client,
+    base_url: String,
+    cache: Mutex<LruCache<PokemonKey, (PokemonStat, Instant)>>,
+    cache_ttl: Duration,
+    in_flight: Mutex<HashMap<PokemonKey, Arc<OnceCell<PokemonStat>>>>,
+}
+
+// Retires a shared fetch once its caller is done with it, even if the caller is cancelled, so
+// that later misses start a new one.
+struct InFlight<'a> {
+    api: &'a PokeApi,
+    key: &'a PokemonKey,
+    cell: Arc<OnceCell<PokemonStat>>,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.api.in_flight.lock()
+            .expect("Pokemon fetch lock should not be poisoned.");
+        if in_flight.get(self.key).is_some_and(|cell| Arc::ptr_eq(cell, &self.cell)) {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+impl PokeApi {
+    pub fn new(config: PokeApiConfig) -> reqwest::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: config.base_url,
+            cache: Mutex::new(LruCache::new(config.cache_capacity)),
+            cache_ttl: config.cache_ttl,
+            in_flight: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn cached(&self, key: &PokemonKey) -> Option<PokemonStat> {
+        let mut cache = self.cache.lock()
+            .expect("Pokemon cache lock should not be poisoned.");
+        match cache.get(key) {
+            Some((stat, fetched)) if fetched.elapsed() < self.cache_ttl => Some(stat.clone()),
+            Some(_) => {
+                cache.pop(key); // Expired.
+                None
+            },
+            None => None,
+        }
+    }
+
+    async fn fetch(&self, key: &PokemonKey) -> Result<PokemonStat, AppError> {
+        let poke_url = format!("{}/api/v2/pokemon/{}/", self.base_url, key);
+        let stat = self.client.get(poke_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PokemonStat>()
+            .await?;
+
+        // Cache under both the id and the name, whichever was asked for.
+        let mut cache = self.cache.lock()
+            .expect("Pokemon cache lock should not be poisoned.");
+        let fetched = Instant::now();
+        cache.put(PokemonKey::Id(stat.id), (stat.clone(), fetched));
+        cache.put(PokemonKey::Name(stat.name.clone()), (stat.clone(), fetched));
+        Ok(stat)
+    }
+}
+
+#[async_trait]
+impl PokemonSource for PokeApi {
+    async fn get_pokemon_stats(&self, key: &PokemonKey) -> Result<PokemonStat, AppError> {
+        let key = key.normalize()?;
+        if let Some(stat) = self.cached(&key) {
+            return Ok(stat);
+        }
+
+        let cell = self.in_flight.lock()
+            .expect("Pokemon fetch lock should not be poisoned.")
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let in_flight = InFlight { api: self, key: &key, cell };
+        // A failed fetch leaves the cell empty, and the next caller waiting on it tries again.
+        // The cache is checked again, in case a fetch finished since the first check.
+        let stat = in_flight.cell.get_or_try_init(|| async {
+            match self.cached(&key) {
+                Some(stat) => Ok(stat),
+                None => self.fetch(&key).await,
+            }
+        }).await?;
+        Ok(stat.clone())
+    }
 }
 
 // Display Pokemon weight, in kilograms.
-async fn weight(Path(poke_id): Path<u64>) -> Result<String, (StatusCode, String)> {
-    get_pokemon_stats(poke_id).await
+async fn weight(
+    State(source): State<Arc<dyn PokemonSource>>,
+    Path(poke_id): Path<u64>,
+) -> Result<String, AppError> {
+    source.get_pokemon_stats(&PokemonKey::Id(poke_id)).await
         .map(|poke_stat| match (poke_stat.weight / 10, poke_stat.weight % 10) {
             (weight, 0) => weight.to_string(),
             (div, md) => format!("{}.{}", div, md)
         })
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))
 }
 
-async fn drop_pokemon(Path(poke_id): Path<u64>) -> Result<String, (StatusCode, String)> {
-    const GRAVITY: f64 = 9.825;
-    const HEIGHT: f64 = 10.0;
+// Gravity (m/s²) and air density (kg/m³) to drop a Pokémon in.
+struct Environment {
+    gravity: f64,
+    air_density: f64,
+}
+
+impl Environment {
+    // The original challenge's gravity, with Earth's air.
+    const DEFAULT: Environment = Environment { gravity: 9.825, air_density: 1.225 };
+
+    fn preset(name: &str) -> Option<Environment> {
+        match name.to_ascii_lowercase().as_str() {
+            "earth" => Some(Environment { gravity: 9.80665, air_density: 1.225 }),
+            "moon" => Some(Environment { gravity: 1.62, air_density: 0.0 }),
+            "mars" => Some(Environment { gravity: 3.72076, air_density: 0.020 }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DropParams {
+    /// Drop height, in metres.
+    height: Option<f64>,
+    /// A preset (earth, moon or mars), or an acceleration in m/s².
+    gravity: Option<String>,
+    /// Apply quadratic air drag, treating the Pokémon as a sphere as wide as it is tall.
+    #[serde(default)]
+    drag: bool,
+    /// Overrides the air density of the gravity preset, in kg/m³.
+    air_density: Option<f64>,
+    drag_coefficient: Option<f64>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DropView {
+    /// Just the momentum, as plain text, as it has always been returned.
+    Momentum,
+    /// Every quantity, as JSON.
+    Full,
+}
+
+#[derive(Deserialize)]
+struct DropViewParams {
+    /// Defaults to `full` for clients that accept JSON, and `momentum` otherwise.
+    view: Option<DropView>,
+}
+
+#[derive(Serialize)]
+struct DropResult {
+    impact_speed: f64,   // m/s
+    momentum: f64,       // kg·m/s
+    kinetic_energy: f64, // J
+    time_to_impact: f64, // s
+}
+
+const DEFAULT_DROP_HEIGHT: f64 = 10.0;
+const SPHERE_DRAG_COEFFICIENT: f64 = 0.47;
+
+fn positive_param(name: &str, value: f64, allow_zero: bool) -> Result<f64, AppError> {
+    if value.is_finite() && (value > 0.0 || (allow_zero && value == 0.0)) {
+        Ok(value)
+    } else {
+        Err(AppError::BadRequest(format!("Invalid {}: {}", name, value)))
+    }
+}
+
+// Validated drop parameters, shared by every Pokémon in a batch.
+struct DropSetup {
+    height: f64,
+    gravity: f64,
+    air_density: f64,
+    drag_coefficient: f64,
+    drag: bool,
+}
+
+fn drop_setup(params: &DropParams) -> Result<DropSetup, AppError> {
+    let height = positive_param("height", params.height.unwrap_or(DEFAULT_DROP_HEIGHT), false)?;
+    let env = match params.gravity.as_deref() {
+        None => Environment::DEFAULT,
+        Some(g) => match Environment::preset(g) {
+            Some(env) => env,
+            None => {
+                let gravity = g.parse::<f64>()
+                    .map_err(|_| AppError::BadRequest(format!("Unknown gravity: {}", g)))?;
+                Environment { gravity: positive_param("gravity", gravity, false)?, ..Environment::DEFAULT }
+            },
+        },
+    };
+    let air_density = positive_param("air_density", params.air_density.unwrap_or(env.air_density), true)?;
+    let drag_coefficient = positive_param(
+        "drag_coefficient",
+        params.drag_coefficient.unwrap_or(SPHERE_DRAG_COEFFICIENT),
+        true,
+    )?;
+    Ok(DropSetup { height, gravity: env.gravity, air_density, drag_coefficient, drag: params.drag })
+}
+
+fn simulate_drop(poke_stat: &PokemonStat, setup: &DropSetup) -> Result<DropResult, AppError> {
+    let mass = poke_stat.weight as f64 * 0.1;
+    let (height, g) = (setup.height, setup.gravity);
+
+    // Drag force is k * v², from the cross-section of a sphere with the Pokémon's height.
+    let diameter = poke_stat.height as f64 * 0.1;
+    let area = std::f64::consts::PI * (diameter / 2.0).powi(2);
+    let k = if setup.drag { 0.5 * setup.air_density * setup.drag_coefficient * area } else { 0.0 };
+
+    let (impact_speed, time_to_impact) = if k == 0.0 {
+        let time = (2.0 * height / g).sqrt();
+        (g * time, time)
+    } else {
+        if mass == 0.0 {
+            return Err(AppError::UnprocessableEntity("Cannot apply drag to a weightless Pokémon".into()));
+        }
+        // Closed-form solution for a fall from rest with quadratic drag.
+        let terminal = (mass * g / k).sqrt();
+        let x = g * height / terminal.powi(2);
+        let speed = terminal * (1.0 - (-2.0 * x).exp()).sqrt();
+        // acosh(e^x) approaches x + ln 2, before e^x overflows.
+        let time = terminal / g * if x > 20.0 { x + std::f64::consts::LN_2 } else { x.exp().acosh() };
+        (speed, time)
+    };
+
+    Ok(DropResult {
+        impact_speed,
+        momentum: mass * impact_speed,
+        kinetic_energy: 0.5 * mass * impact_speed.powi(2),
+        time_to_impact,
+    })
+}
+
+// Responds with only the momentum, unless asked for the full view or the client accepts JSON.
+async fn drop_pokemon(
+    State(source): State<Arc<dyn PokemonSource>>,
+    Path(poke_id): Path<u64>,
+    Query(params): Query<DropParams>,
+    Query(DropViewParams { view }): Query<DropViewParams>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let setup = drop_setup(&params)?;
+    let poke_stat = source.get_pokemon_stats(&PokemonKey::Id(poke_id)).await?;
+    let result = simulate_drop(&poke_stat, &setup)?;
+
+    let view = view.unwrap_or_else(|| {
+        let wants_json = headers.get(ACCEPT)
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|h| h.contains("application/json"));
+        if wants_json { DropView::Full } else { DropView::Momentum }
+    });
+    match view {
+        DropView::Full => Ok(Json(result).into_response()),
+        DropView::Momentum => Ok(result.momentum.to_string().into_response()),
+    }
+}
 
-    get_pokemon_stats(poke_id).await
-        .map(|poke_stat| {
-            let weight = poke_stat.weight as f64 * 0.1;
-            let speed = GRAVITY * (2.0 * HEIGHT / GRAVITY).sqrt();
-            let momemtum = weight * speed;
-            momemtum.to_string()
+/// Upper bound on the number of Pokémon in one batch request.
+const MAX_BATCH_SIZE: usize = 100;
+/// Number of upstream lookups a batch request keeps in flight.
+const BATCH_CONCURRENCY: usize = 8;
+
+#[derive(Serialize)]
+struct PokemonWeight {
+    id: u64,
+    name: String,
+    weight: f64, // kg
+}
+
+impl From<&PokemonStat> for PokemonWeight {
+    fn from(stat: &PokemonStat) -> Self {
+        Self { id: stat.id, name: stat.name.clone(), weight: stat.weight as f64 * 0.1 }
+    }
+}
+
+#[derive(Serialize)]
+struct ItemError {
+    status: u16,
+    code: &'static str,
+    detail: String,
+}
+
+impl From<AppError> for ItemError {
+    fn from(e: AppError) -> Self {
+        e.log();
+        Self { status: e.status().as_u16(), code: e.code(), detail: e.detail() }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchItem {
+    Found {
+        query: PokemonKey,
+        #[serde(flatten)]
+        pokemon: PokemonWeight,
+        momentum: f64,
+    },
+    Failed {
+        query: PokemonKey,
+        error: ItemError,
+    },
+}
+
+#[derive(Serialize)]
+struct BatchResult {
+    results: Vec<BatchItem>,
+}
+
+// Look up every Pokémon, keeping the request order, with failures reported per item.
+async fn batch(
+    State(source): State<Arc<dyn PokemonSource>>,
+    Query(params): Query<DropParams>,
+    Json(queries): Json<Vec<PokemonKey>>,
+) -> Result<Json<BatchResult>, AppError> {
+    if queries.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(
+            format!("Batch of {} exceeds the limit of {}", queries.len(), MAX_BATCH_SIZE)
+        ));
+    }
+    let setup = drop_setup(&params)?;
+
+    let results = stream::iter(queries)
+        .map(|query| {
+            let (source, setup) = (&source, &setup);
+            async move {
+                let found = source.get_pokemon_stats(&query).await
+                    .and_then(|stat| Ok((simulate_drop(&stat, setup)?, stat)));
+                match found {
+                    Ok((drop, stat)) => BatchItem::Found {
+                        query,
+                        pokemon: PokemonWeight::from(&stat),
+                        momentum: drop.momentum,
+                    },
+                    Err(e) => BatchItem::Failed { query, error: e.into() },
+                }
+            }
         })
-        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(Json(BatchResult { results }))
+}
+
+#[derive(Serialize)]
+struct Comparison {
+    pokemon: [PokemonWeight; 2],
+    /// Name of the heavier Pokémon, or null if they weigh the same.
+    heavier: Option<String>,
+    /// Heavier weight over lighter weight, or null if the lighter one is weightless.
+    ratio: Option<f64>,
+}
+
+async fn compare(
+    State(source): State<Arc<dyn PokemonSource>>,
+    Path((a, b)): Path<(String, String)>,
+) -> Result<Json<Comparison>, AppError> {
+    let (a, b) = (PokemonKey::Name(a), PokemonKey::Name(b));
+    let (a, b) = futures::try_join!(source.get_pokemon_stats(&a), source.get_pokemon_stats(&b))?;
+
+    let (heavier, lighter) = if a.weight >= b.weight { (&a, &b) } else { (&b, &a) };
+    Ok(Json(Comparison {
+        heavier: (a.weight != b.weight).then(|| heavier.name.clone()),
+        ratio: (lighter.weight > 0).then(|| heavier.weight as f64 / lighter.weight as f64),
+        pokemon: [PokemonWeight::from(&a), PokemonWeight::from(&b)],
+    }))
 }
 
-pub fn pokemon_router() -> Router {
+pub fn pokemon_router(source: Arc<dyn PokemonSource>) -> Router {
     Router::new().route("/weight/:poke_id", get(weight))
         .route("/drop/:poke_id", get(drop_pokemon))
+        .route("/batch", post(batch))
+        .route("/compare/:a/:b", get(compare))
+        .with_state(source)
 }
\ No newline at end of file
@@ -12,4 +12,6 @@ pub mod day18;
 pub mod day19;
 pub mod day20;
 pub mod day21;
-pub mod day22;
\ No newline at end of file
+pub mod day22;
+pub mod order_db;
+pub mod reindeer_db;
\ No newline at end of file
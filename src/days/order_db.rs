@@ -0,0 +1,348 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, FromRow, PgPool, QueryBuilder};
+use tokio::sync::RwLock;
+
+use crate::error::AppError;
+
+#[derive(Clone, FromRow, Serialize, Deserialize)]
+pub struct Order {
+    pub id: i32,
+    pub region_id: i32,
+    pub gift_name: String,
+    pub quantity: i32,
+}
+
+#[derive(Clone, Deserialize, FromRow, Serialize)]
+pub struct Region {
+    pub id: i32,
+    pub name: String
+}
+
+#[derive(Deserialize, FromRow, Serialize)]
+pub struct TotalByRegion {
+    pub region: String,
+    pub total: i64,
+}
+
+#[derive(Deserialize, FromRow, Serialize)]
+pub struct TopGiftsByRegion {
+    pub region: String,
+    pub top_gifts: Vec<String>,
+}
+
+/// Storage for the gift orders of days 13 and 18.
+#[async_trait]
+pub trait OrderDb: Send + Sync {
+    /// Round-trip a constant through the store.
+    async fn ping(&self) -> Result<i32, AppError>;
+    /// Drop all orders, leaving regions alone.
+    async fn reset_orders(&self) -> Result<(), AppError>;
+    /// Drop all orders and regions.
+    async fn reset_orders_and_regions(&self) -> Result<(), AppError>;
+    async fn insert_orders(&self, orders: &[Order]) -> Result<(), AppError>;
+    async fn insert_regions(&self, regions: &[Region]) -> Result<(), AppError>;
+    /// Sum of the quantity of every order.
+    async fn total_quantity(&self) -> Result<i64, AppError>;
+    /// Gift with the highest total quantity, if there are any orders. Ties go to the first name.
+    async fn most_popular_gift(&self) -> Result<Option<String>, AppError>;
+    /// Total quantity for every region with orders, sorted by region name, then id.
+    async fn totals_by_region(&self) -> Result<Vec<TotalByRegion>, AppError>;
+    /// Up to `limit` gifts for every region, by descending quantity, then name, sorted by region
+    /// name, then id. A negative limit is a bad request.
+    async fn top_gifts_by_region(&self, limit: i64) -> Result<Vec<TopGiftsByRegion>, AppError>;
+}
+
+#[derive(Clone)]
+pub struct PgOrderDb {
+    pool: PgPool,
+}
+
+impl PgOrderDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+const RESET_ORDERS_QUERY: &str = r"
+    DROP TABLE IF EXISTS orders;
+    CREATE TABLE orders (
+        id INT PRIMARY KEY,
+        region_id INT,
+        gift_name VARCHAR(50),
+        quantity INT
+    );
+";
+
+const RESET_ORDERS_AND_REGIONS_QUERY: &str = r"
+    DROP TABLE IF EXISTS regions;
+    DROP TABLE IF EXISTS orders;
+
+    CREATE TABLE regions (
+        id INT PRIMARY KEY,
+        name VARCHAR(50)
+    );
+
+    CREATE TABLE orders (
+        id INT PRIMARY KEY,
+        region_id INT,
+        gift_name VARCHAR(50),
+        quantity INT
+    );
+";
+
+const MOST_POPULAR_QUERY: &str = r#"
+    SELECT gift_name
+    FROM (SELECT gift_name, SUM(quantity) AS total
+        FROM orders
+        GROUP BY gift_name) AS totals
+    ORDER BY total DESC, gift_name COLLATE "C"
+    LIMIT 1;
+"#;
+
+// Names are compared by bytes, `COLLATE "C"` in SQL, so both backends sort them the same way.
+// Postgres would reject a negative LIMIT with a database error, so check it up front.
+fn check_limit(limit: i64) -> Result<usize, AppError> {
+    usize::try_from(limit).map_err(|_| AppError::BadRequest("Limit must not be negative".into()))
+}
+
+#[async_trait]
+impl OrderDb for PgOrderDb {
+    async fn ping(&self) -> Result<i32, AppError> {
+        Ok(sqlx::query_scalar("SELECT 20231213")
+            .fetch_one(&self.pool)
+            .await?)
+    }
+
+    async fn reset_orders(&self) -> Result<(), AppError> {
+        // Use pool directly to execute multiple statements.
+        self.pool.execute(RESET_ORDERS_QUERY).await?;
+        Ok(())
+    }
+
+    async fn reset_orders_and_regions(&self) -> Result<(), AppError> {
+        self.pool.execute(RESET_ORDERS_AND_REGIONS_QUERY).await?;
+        Ok(())
+    }
+
+    async fn insert_orders(&self, orders: &[Order]) -> Result<(), AppError> {
+        // Skip if orders is empty.
+        if orders.is_empty() {
+            return Ok(());
+        }
+
+        // Use a QueryBuilder to add multiple tuple values.
+        let mut builder: QueryBuilder<sqlx::Postgres> =
+            QueryBuilder::new("INSERT INTO orders (id, region_id, gift_name, quantity) ");
+        builder.push_values(orders, |mut row, order| {
+            row.push_bind(order.id)
+                .push_bind(order.region_id)
+                .push_bind(&order.gift_name)
+                .push_bind(order.quantity);
+        });
+        builder.build()
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_regions(&self, regions: &[Region]) -> Result<(), AppError> {
+        if regions.is_empty() {
+            return Ok(());
+        }
+
+        let mut builder: QueryBuilder<sqlx::Postgres> =
+            QueryBuilder::new("INSERT INTO regions (id, name) ");
+        builder.push_values(regions, |mut row, region| {
+            row.push_bind(region.id)
+                .push_bind(&region.name);
+        });
+        builder.build()
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn total_quantity(&self) -> Result<i64, AppError> {
+        Ok(sqlx::query_scalar("SELECT COALESCE(SUM(quantity), 0) FROM orders;")
+            .fetch_one(&self.pool)
+            .await?)
+    }
+
+    async fn most_popular_gift(&self) -> Result<Option<String>, AppError> {
+        Ok(sqlx::query_scalar(MOST_POPULAR_QUERY)
+            .fetch_optional(&self.pool)
+            .await?)
+    }
+
+    async fn totals_by_region(&self) -> Result<Vec<TotalByRegion>, AppError> {
+        Ok(sqlx::query_as(
+            r#"SELECT rs.name AS region, SUM(os.quantity) AS total
+                FROM orders os
+                JOIN regions as rs ON os.region_id = rs.id
+                GROUP BY rs.id
+                ORDER BY rs.name COLLATE "C", rs.id;"#
+        )
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    async fn top_gifts_by_region(&self, limit: i64) -> Result<Vec<TopGiftsByRegion>, AppError> {
+        check_limit(limit)?;
+        Ok(sqlx::query_as(
+            r#"WITH gifts AS
+                    (SELECT os.region_id, os.gift_name, SUM(os.quantity) AS sum
+                    FROM orders os
+                    GROUP BY os.region_id, os.gift_name)
+                SELECT
+                    rs.name AS region,
+                    COALESCE(
+                        ARRAY_AGG(ga.gift_name ORDER BY ga.sum DESC, ga.gift_name COLLATE "C")
+                            FILTER(WHERE ga.gift_name IS NOT NULL),
+                        '{}') AS top_gifts
+                FROM regions rs
+                LEFT JOIN LATERAL (SELECT g.region_id, g.gift_name, g.sum
+                    FROM gifts g
+                    WHERE rs.id = g.region_id
+                    ORDER BY g.sum DESC, g.gift_name COLLATE "C"
+                    LIMIT $1 ) ga ON rs.id = ga.region_id
+                GROUP BY rs.id, rs.name
+                ORDER BY rs.name COLLATE "C", rs.id;"#
+        )
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+}
+
+#[derive(Default)]
+struct OrderTables {
+    orders: BTreeMap<i32, Order>,
+    regions: BTreeMap<i32, Region>,
+}
+
+/// In-memory order store, for running without Postgres.
+#[derive(Default)]
+pub struct MemoryOrderDb {
+    tables: RwLock<OrderTables>,
+}
+
+impl MemoryOrderDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// Sum quantities by gift name, for the given orders.
+fn gift_totals<'a>(orders: impl Iterator<Item = &'a Order>) -> HashMap<&'a str, i64> {
+    let mut totals = HashMap::new();
+    for order in orders {
+        *totals.entry(order.gift_name.as_str()).or_insert(0i64) += order.quantity as i64;
+    }
+    totals
+}
+
+// Check primary keys before inserting anything, so a failed insert leaves the table untouched.
+fn check_unique_ids<T>(
+    table: &BTreeMap<i32, T>,
+    ids: impl Iterator<Item = i32>,
+    table_name: &str,
+) -> Result<(), AppError> {
+    let mut seen = HashSet::new();
+    for id in ids {
+        if table.contains_key(&id) || !seen.insert(id) {
+            return Err(AppError::Conflict(format!("Duplicate id {} in {}", id, table_name)));
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl OrderDb for MemoryOrderDb {
+    async fn ping(&self) -> Result<i32, AppError> {
+        Ok(20231213)
+    }
+
+    async fn reset_orders(&self) -> Result<(), AppError> {
+        self.tables.write().await.orders.clear();
+        Ok(())
+    }
+
+    async fn reset_orders_and_regions(&self) -> Result<(), AppError> {
+        *self.tables.write().await = OrderTables::default();
+        Ok(())
+    }
+
+    async fn insert_orders(&self, orders: &[Order]) -> Result<(), AppError> {
+        let mut tables = self.tables.write().await;
+        check_unique_ids(&tables.orders, orders.iter().map(|o| o.id), "orders")?;
+        tables.orders.extend(orders.iter().map(|o| (o.id, o.clone())));
+        Ok(())
+    }
+
+    async fn insert_regions(&self, regions: &[Region]) -> Result<(), AppError> {
+        let mut tables = self.tables.write().await;
+        check_unique_ids(&tables.regions, regions.iter().map(|r| r.id), "regions")?;
+        tables.regions.extend(regions.iter().map(|r| (r.id, r.clone())));
+        Ok(())
+    }
+
+    async fn total_quantity(&self) -> Result<i64, AppError> {
+        let tables = self.tables.read().await;
+        Ok(tables.orders.values().map(|o| o.quantity as i64).sum())
+    }
+
+    async fn most_popular_gift(&self) -> Result<Option<String>, AppError> {
+        let tables = self.tables.read().await;
+        // Break ties by name, so the result is stable.
+        Ok(gift_totals(tables.orders.values())
+            .into_iter()
+            .min_by_key(|&(name, total)| (Reverse(total), name))
+            .map(|(name, _)| name.to_string()))
+    }
+
+    async fn totals_by_region(&self) -> Result<Vec<TotalByRegion>, AppError> {
+        let tables = self.tables.read().await;
+        let mut totals = BTreeMap::<i32, i64>::new();
+        for order in tables.orders.values() {
+            if tables.regions.contains_key(&order.region_id) {
+                *totals.entry(order.region_id).or_default() += order.quantity as i64;
+            }
+        }
+
+        let mut result: Vec<TotalByRegion> = totals.into_iter()
+            .map(|(id, total)| TotalByRegion { region: tables.regions[&id].name.clone(), total })
+            .collect();
+        // Stable, so regions with the same name stay in id order.
+        result.sort_by(|a, b| a.region.cmp(&b.region));
+        Ok(result)
+    }
+
+    async fn top_gifts_by_region(&self, limit: i64) -> Result<Vec<TopGiftsByRegion>, AppError> {
+        let tables = self.tables.read().await;
+        let limit = check_limit(limit)?;
+
+        let mut result: Vec<TopGiftsByRegion> = tables.regions.values()
+            .map(|region| {
+                let mut gifts: Vec<(&str, i64)> = gift_totals(
+                    tables.orders.values().filter(|o| o.region_id == region.id)
+                ).into_iter().collect();
+                gifts.sort_by_key(|&(name, total)| (Reverse(total), name));
+                TopGiftsByRegion {
+                    region: region.name.clone(),
+                    top_gifts: gifts.into_iter()
+                        .take(limit)
+                        .map(|(name, _)| name.to_string())
+                        .collect(),
+                }
+            }).collect();
+        // Stable, so regions with the same name stay in id order.
+        result.sort_by(|a, b| a.region.cmp(&b.region));
+        Ok(result)
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, Executor, FromRow, PgPool};
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::error::AppError;
+
+#[derive(Clone, Deserialize, FromRow, Serialize)]
+pub struct Reindeer {
+    pub name: String,
+    pub strength: i64,
+    #[serde(default)]
+    pub speed: f64,
+    #[serde(default)]
+    pub height: i64,
+    #[serde(default)]
+    pub antler_width: i64,
+    #[serde(default)]
+    pub snow_magic_power: i64,
+    #[serde(default)]
+    pub favorite_food: String,
+    #[serde(rename="cAnD13s_3ATeN-yesT3rdAy", alias="candies_eaten_yesterday", default)]
+    pub candies_eaten_yesterday: i64,
+}
+
+impl Reindeer {
+    /// Reject the measurements no reindeer could have.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let counts = [
+            ("strength", self.strength),
+            ("height", self.height),
+            ("antler_width", self.antler_width),
+            ("snow_magic_power", self.snow_magic_power),
+            ("candies_eaten_yesterday", self.candies_eaten_yesterday),
+        ];
+        if let Some((field, _)) = counts.iter().find(|(_, value)| *value < 0) {
+            return Err(AppError::BadRequest(format!("{} of {} must not be negative", field, self.name)));
+        }
+        if !self.speed.is_finite() || self.speed < 0.0 {
+            return Err(AppError::BadRequest(format!("speed of {} must not be negative", self.name)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, FromRow, Serialize)]
+pub struct StoredReindeer {
+    pub id: i64,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub reindeer: Reindeer,
+}
+
+#[derive(Clone, FromRow, Serialize)]
+pub struct ContestRecord {
+    pub id: i64,
+    pub held_at: DateTime<Utc>,
+    /// Name of the winning reindeer, by category.
+    #[sqlx(json)]
+    pub winners: BTreeMap<String, String>,
+}
+
+/// Storage for the reindeer registry and contest history of day 4.
+#[async_trait]
+pub trait ReindeerDb: Send + Sync {
+    async fn create_reindeer(&self, reindeer: &Reindeer) -> Result<StoredReindeer, AppError>;
+    async fn get_reindeer(&self, id: i64) -> Result<Option<StoredReindeer>, AppError>;
+    /// Every stored reindeer, sorted by id.
+    async fn list_reindeer(&self) -> Result<Vec<StoredReindeer>, AppError>;
+    /// Replace a reindeer, returning `None` if there is no reindeer with that id.
+    async fn update_reindeer(&self, id: i64, reindeer: &Reindeer) -> Result<Option<StoredReindeer>, AppError>;
+    /// Returns whether there was a reindeer to delete.
+    async fn delete_reindeer(&self, id: i64) -> Result<bool, AppError>;
+    async fn record_contest(&self, winners: &BTreeMap<String, String>) -> Result<ContestRecord, AppError>;
+    /// Up to `limit` contests, most recent first.
+    async fn contest_history(&self, limit: i64) -> Result<Vec<ContestRecord>, AppError>;
+}
+
+pub struct PgReindeerDb {
+    pool: PgPool,
+    schema: OnceCell<()>,
+}
+
+impl PgReindeerDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool, schema: OnceCell::new() }
+    }
+
+    // The registry outlives restarts, so its tables are created once rather than reset.
+    async fn pool(&self) -> Result<&PgPool, AppError> {
+        self.schema.get_or_try_init(|| async {
+            self.pool.execute(CREATE_TABLES_QUERY).await.map(|_| ())
+        }).await?;
+        Ok(&self.pool)
+    }
+}
+
+const CREATE_TABLES_QUERY: &str = r"
+    CREATE TABLE IF NOT EXISTS reindeer (
+        id BIGSERIAL PRIMARY KEY,
+        name TEXT NOT NULL,
+        strength BIGINT NOT NULL,
+        speed DOUBLE PRECISION NOT NULL,
+        height BIGINT NOT NULL,
+        antler_width BIGINT NOT NULL,
+        snow_magic_power BIGINT NOT NULL,
+        favorite_food TEXT NOT NULL,
+        candies_eaten_yesterday BIGINT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS contests (
+        id BIGSERIAL PRIMARY KEY,
+        held_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        winners JSONB NOT NULL
+    );
+";
+
+const REINDEER_COLUMNS: &str = "id, name, strength, speed, height, antler_width, \
+    snow_magic_power, favorite_food, candies_eaten_yesterday";
+
+#[async_trait]
+impl ReindeerDb for PgReindeerDb {
+    async fn create_reindeer(&self, reindeer: &Reindeer) -> Result<StoredReindeer, AppError> {
+        Ok(sqlx::query_as(&format!(
+            r"INSERT INTO reindeer (name, strength, speed, height, antler_width,
+                    snow_magic_power, favorite_food, candies_eaten_yesterday)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING {};",
+            REINDEER_COLUMNS,
+        ))
+            .bind(&reindeer.name)
+            .bind(reindeer.strength)
+            .bind(reindeer.speed)
+            .bind(reindeer.height)
+            .bind(reindeer.antler_width)
+            .bind(reindeer.snow_magic_power)
+            .bind(&reindeer.favorite_food)
+            .bind(reindeer.candies_eaten_yesterday)
+            .fetch_one(self.pool().await?)
+            .await?)
+    }
+
+    async fn get_reindeer(&self, id: i64) -> Result<Option<StoredReindeer>, AppError> {
+        Ok(sqlx::query_as(&format!("SELECT {} FROM reindeer WHERE id = $1;", REINDEER_COLUMNS))
+            .bind(id)
+            .fetch_optional(self.pool().await?)
+            .await?)
+    }
+
+    async fn list_reindeer(&self) -> Result<Vec<StoredReindeer>, AppError> {
+        Ok(sqlx::query_as(&format!("SELECT {} FROM reindeer ORDER BY id;", REINDEER_COLUMNS))
+            .fetch_all(self.pool().await?)
+            .await?)
+    }
+
+    async fn update_reindeer(&self, id: i64, reindeer: &Reindeer) -> Result<Option<StoredReindeer>, AppError> {
+        Ok(sqlx::query_as(&format!(
+            r"UPDATE reindeer
+                SET name = $2, strength = $3, speed = $4, height = $5, antler_width = $6,
+                    snow_magic_power = $7, favorite_food = $8, candies_eaten_yesterday = $9
+                WHERE id = $1
+                RETURNING {};",
+            REINDEER_COLUMNS,
+        ))
+            .bind(id)
+            .bind(&reindeer.name)
+            .bind(reindeer.strength)
+            .bind(reindeer.speed)
+            .bind(reindeer.height)
+            .bind(reindeer.antler_width)
+            .bind(reindeer.snow_magic_power)
+            .bind(&reindeer.favorite_food)
+            .bind(reindeer.candies_eaten_yesterday)
+            .fetch_optional(self.pool().await?)
+            .await?)
+    }
+
+    async fn delete_reindeer(&self, id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM reindeer WHERE id = $1;")
+            .bind(id)
+            .execute(self.pool().await?)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_contest(&self, winners: &BTreeMap<String, String>) -> Result<ContestRecord, AppError> {
+        Ok(sqlx::query_as("INSERT INTO contests (winners) VALUES ($1) RETURNING id, held_at, winners;")
+            .bind(Json(winners))
+            .fetch_one(self.pool().await?)
+            .await?)
+    }
+
+    async fn contest_history(&self, limit: i64) -> Result<Vec<ContestRecord>, AppError> {
+        Ok(sqlx::query_as(
+            "SELECT id, held_at, winners FROM contests ORDER BY held_at DESC, id DESC LIMIT $1;"
+        )
+            .bind(limit)
+            .fetch_all(self.pool().await?)
+            .await?)
+    }
+}
+
+/// Contests the in-memory registry remembers, dropping the oldest past this.
+pub const MAX_MEMORY_CONTESTS: usize = 1000;
+
+#[derive(Default)]
+struct ReindeerTables {
+    // Ids are never reused, as with a Postgres sequence.
+    next_reindeer_id: i64,
+    reindeer: BTreeMap<i64, Reindeer>,
+    next_contest_id: i64,
+    // Oldest first.
+    contests: VecDeque<ContestRecord>,
+}
+
+/// In-memory reindeer registry, for running without Postgres.
+#[derive(Default)]
+pub struct MemoryReindeerDb {
+    tables: RwLock<ReindeerTables>,
+}
+
+impl MemoryReindeerDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ReindeerDb for MemoryReindeerDb {
+    async fn create_reindeer(&self, reindeer: &Reindeer) -> Result<StoredReindeer, AppError> {
+        let mut tables = self.tables.write().await;
+        tables.next_reindeer_id += 1;
+        let id = tables.next_reindeer_id;
+        tables.reindeer.insert(id, reindeer.clone());
+        Ok(StoredReindeer { id, reindeer: reindeer.clone() })
+    }
+
+    async fn get_reindeer(&self, id: i64) -> Result<Option<StoredReindeer>, AppError> {
+        let tables = self.tables.read().await;
+        Ok(tables.reindeer.get(&id).map(|r| StoredReindeer { id, reindeer: r.clone() }))
+    }
+
+    async fn list_reindeer(&self) -> Result<Vec<StoredReindeer>, AppError> {
+        let tables = self.tables.read().await;
+        Ok(tables.reindeer.iter()
+            .map(|(&id, r)| StoredReindeer { id, reindeer: r.clone() })
+            .collect())
+    }
+
+    async fn update_reindeer(&self, id: i64, reindeer: &Reindeer) -> Result<Option<StoredReindeer>, AppError> {
+        let mut tables = self.tables.write().await;
+        Ok(tables.reindeer.get_mut(&id).map(|stored| {
+            *stored = reindeer.clone();
+            StoredReindeer { id, reindeer: reindeer.clone() }
+        }))
+    }
+
+    async fn delete_reindeer(&self, id: i64) -> Result<bool, AppError> {
+        Ok(self.tables.write().await.reindeer.remove(&id).is_some())
+    }
+
+    async fn record_contest(&self, winners: &BTreeMap<String, String>) -> Result<ContestRecord, AppError> {
+        let mut tables = self.tables.write().await;
+        tables.next_contest_id += 1;
+        let record = ContestRecord {
+            id: tables.next_contest_id,
+            held_at: Utc::now(),
+            winners: winners.clone(),
+        };
+        if tables.contests.len() == MAX_MEMORY_CONTESTS {
+            tables.contests.pop_front();
+        }
+        tables.contests.push_back(record.clone());
+        Ok(record)
+    }
+
+    async fn contest_history(&self, limit: i64) -> Result<Vec<ContestRecord>, AppError> {
+        let limit = usize::try_from(limit)
+            .map_err(|_| AppError::BadRequest("Limit must not be negative".into()))?;
+        let tables = self.tables.read().await;
+        Ok(tables.contests.iter().rev().take(limit).cloned().collect())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,232 @@
+use std::fmt;
+
+use axum::{
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+/// Crate-wide error returned by every day router.
+///
+/// Errors are rendered as an RFC 7807 problem document, with a stable `code`
+/// member that clients can branch on.
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    UnprocessableEntity(String),
+    UnsupportedMediaType(String),
+    PayloadTooLarge(String),
+    /// Signed or encrypted data that failed verification.
+    InvalidSignature(String),
+    BadGateway(String),
+    Internal(String),
+    /// Malformed input, with the byte offset of the mistake.
+    Parse { offset: usize, message: String },
+    /// A bad row of tabular input, with its 1-based line and column when known.
+    InvalidRecord { row: u64, column: Option<u64>, message: String },
+    Database(sqlx::Error),
+    Upstream(reqwest::Error),
+    Image(image::ImageError),
+    Io(std::io::Error),
+    Ulid(ulid::DecodeError),
+}
+
+#[derive(Serialize)]
+struct ProblemDocument<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'a str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    row: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<u64>,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::InvalidSignature(_) => StatusCode::BAD_REQUEST,
+            AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Parse { .. } => StatusCode::BAD_REQUEST,
+            AppError::InvalidRecord { .. } => StatusCode::BAD_REQUEST,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Image(_) => StatusCode::BAD_REQUEST,
+            AppError::Io(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Ulid(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Machine-readable error code. These values are part of the public API.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::UnprocessableEntity(_) => "unprocessable_entity",
+            AppError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AppError::PayloadTooLarge(_) => "payload_too_large",
+            AppError::InvalidSignature(_) => "invalid_signature",
+            AppError::BadGateway(_) => "bad_gateway",
+            AppError::Internal(_) => "internal_error",
+            AppError::Parse { .. } => "parse_error",
+            AppError::InvalidRecord { .. } => "invalid_record",
+            AppError::Database(_) => "database_error",
+            AppError::Upstream(_) => "upstream_error",
+            AppError::Image(_) => "invalid_image",
+            AppError::Io(_) => "io_error",
+            AppError::Ulid(_) => "invalid_ulid",
+        }
+    }
+
+    /// The `detail` shown to clients. Server errors can carry SQL errors or upstream URLs,
+    /// so they get a generic message and the full one is only logged.
+    pub fn detail(&self) -> String {
+        match self.status() {
+            StatusCode::BAD_GATEWAY => "An upstream service failed".into(),
+            status if status.is_server_error() => "An internal error occurred".into(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Log server errors, whose details are withheld from clients.
+    pub fn log(&self) {
+        if self.status().is_server_error() {
+            tracing::error!("{}: {}", self.code(), self);
+        }
+    }
+
+    // Extractor rejections carry their own status and a plain-text message.
+    fn from_rejection(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => AppError::NotFound(message),
+            StatusCode::PAYLOAD_TOO_LARGE => AppError::PayloadTooLarge(message),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => AppError::UnsupportedMediaType(message),
+            StatusCode::UNPROCESSABLE_ENTITY => AppError::UnprocessableEntity(message),
+            status if status.is_server_error() => AppError::Internal(message),
+            _ => AppError::BadRequest(message),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(s)
+            | AppError::NotFound(s)
+            | AppError::Conflict(s)
+            | AppError::UnprocessableEntity(s)
+            | AppError::UnsupportedMediaType(s)
+            | AppError::PayloadTooLarge(s)
+            | AppError::InvalidSignature(s)
+            | AppError::BadGateway(s)
+            | AppError::Internal(s) => f.write_str(s),
+            AppError::Parse { offset, message } => write!(f, "{} at byte {}", message, offset),
+            AppError::InvalidRecord { row, column: Some(column), message } =>
+                write!(f, "{} at row {}, column {}", message, row, column),
+            AppError::InvalidRecord { row, column: None, message } => write!(f, "{} at row {}", message, row),
+            AppError::Database(e) => write!(f, "DB error: {}", e),
+            AppError::Upstream(e) => write!(f, "Upstream error: {}", e),
+            AppError::Image(e) => write!(f, "Unable to decode image: {}", e),
+            AppError::Io(e) => write!(f, "I/O error: {}", e),
+            AppError::Ulid(e) => write!(f, "Unable to parse Ulid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        self.log();
+
+        let (row, column) = match self {
+            AppError::InvalidRecord { row, column, .. } => (Some(row), column),
+            _ => (None, None),
+        };
+        let problem = ProblemDocument {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Unknown Error"),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            offset: match self {
+                AppError::Parse { offset, .. } => Some(offset),
+                _ => None,
+            },
+            row,
+            column,
+        };
+        let mut response = (status, Json(problem)).into_response();
+        response.headers_mut()
+            .insert(CONTENT_TYPE, "application/problem+json".parse().unwrap());
+        response
+    }
+}
+
+macro_rules! from_rejection {
+    ($($rejection:ty),*) => {$(
+        impl From<$rejection> for AppError {
+            fn from(rejection: $rejection) -> Self {
+                AppError::from_rejection(rejection.status(), rejection.body_text())
+            }
+        }
+    )*};
+}
+
+from_rejection!(JsonRejection, QueryRejection, PathRejection, MultipartRejection, MultipartError);
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() =>
+                AppError::Conflict(db_err.message().to_string()),
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::Upstream(e)
+    }
+}
+
+impl From<image::ImageError> for AppError {
+    fn from(e: image::ImageError) -> Self {
+        AppError::Image(e)
+    }
+}
+
+// Also covers `tar`, which reports all of its errors through `std::io::Error`.
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e)
+    }
+}
+
+impl From<ulid::DecodeError> for AppError {
+    fn from(e: ulid::DecodeError) -> Self {
+        AppError::Ulid(e)
+    }
+}
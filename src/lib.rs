@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use axum::{
+    http::status::StatusCode,
+    routing::get,
+    Router
+};
+use sqlx::PgPool;
+
+pub mod days;
+pub mod error;
+pub mod extract;
+use days::*;
+use days::order_db::{MemoryOrderDb, OrderDb, PgOrderDb};
+use days::reindeer_db::{MemoryReindeerDb, PgReindeerDb, ReindeerDb};
+
+async fn hello_world() -> &'static str {
+    "Hello, Santa!"
+}
+
+async fn internal_service_error() -> StatusCode {
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Storage backends for the routers that persist data.
+#[derive(Clone)]
+pub struct Storage {
+    pub orders: Arc<dyn OrderDb>,
+    pub reindeer: Arc<dyn ReindeerDb>,
+}
+
+impl Storage {
+    pub fn postgres(pool: PgPool) -> Self {
+        Self {
+            orders: Arc::new(PgOrderDb::new(pool.clone())),
+            reindeer: Arc::new(PgReindeerDb::new(pool)),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self {
+            orders: Arc::new(MemoryOrderDb::new()),
+            reindeer: Arc::new(MemoryReindeerDb::new()),
+        }
+    }
+}
+
+/// Builds the router for every day. Shared by the Shuttle and standalone entry points.
+pub fn build_router(
+    storage: &Storage,
+    ws_sessions: &day19::WsSessions,
+    xor_cube: day1::XorCubeConfig,
+    recipe_cookies: day7::RecipeCookieConfig,
+    pokeapi: day8::PokeApiConfig,
+) -> Router {
+    Router::new().route("/", get(hello_world))
+        .route("/-1/error", get(internal_service_error))
+        .nest("/1", day1::xor_cube_router(xor_cube))
+        .nest("/4", day4::serdeer_router(storage.reindeer.clone()))
+        .nest("/6", day6::elf_router())
+        .nest("/7", day7::cookie_router(recipe_cookies))
+        .nest("/8", day8::pokemon_router(Arc::new(
+            day8::PokeApi::new(pokeapi)
+                .expect("HTTP client should build with the default TLS backend.")
+        )))
+        .nest("/11", day11::ornament_router())
+        .nest("/12", day12::timekeeper_router())
+        .nest("/13", day13::gift_order_router(storage.orders.clone()))
+        .nest("/14", day14::html_reindeer_route())
+        .nest("/15", day15::nice_password_router())
+        .nest("/18", day18::gift_order_router2(storage.orders.clone()))
+        .nest("/19", day19::ws_games_router(ws_sessions.clone()))
+        .nest("/20", day20::archive_router())
+        .nest("/21", day21::world_coord_router(day21::PHOTON_BASE_URL))
+        .nest("/22", day22::final_router())
+}
\ No newline at end of file
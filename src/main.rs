@@ -1,43 +1,47 @@
-use axum::{
-    http::status::StatusCode,
-    routing::get,
-    Router
+use cch23_scd91::{
+    build_router,
+    days::{day1::XorCubeConfig, day19::WsSessions, day7::RecipeCookieConfig, day8::PokeApiConfig},
+    Storage,
 };
+use shuttle_secrets::SecretStore;
 use sqlx::PgPool;
 
-mod days;
-use days::*;
-
-async fn hello_world() -> &'static str {
-    "Hello, Santa!"
-}
-
-async fn internal_service_error() -> StatusCode {
-    StatusCode::INTERNAL_SERVER_ERROR
-}
-
 #[shuttle_runtime::main]
 async fn main(
     #[shuttle_shared_db::Postgres(local_uri="postgres://{secrets.USERSPEC}@localhost:5432/cch23")]
-    pool: PgPool
+    pool: PgPool,
+    #[shuttle_secrets::Secrets] secrets: SecretStore,
 ) -> shuttle_axum::ShuttleAxum {
-    let router = Router::new().route("/", get(hello_world))
-        .route("/-1/error", get(internal_service_error))
-        .nest("/1", day1::xor_cube_router())
-        .nest("/4", day4::serdeer_router())
-        .nest("/6", day6::elf_router())
-        .nest("/7", day7::cookie_router())
-        .nest("/8", day8::pokemon_router())
-        .nest("/11", day11::ornament_router())
-        .nest("/12", day12::timekeeper_router())
-        .nest("/13", day13::gift_order_router(pool.clone()))
-        .nest("/14", day14::html_reindeer_route())
-        .nest("/15", day15::nice_password_router())
-        .nest("/18", day18::gift_order_router2(pool.clone()))
-        .nest("/19", day19::ws_games_router())
-        .nest("/20", day20::archive_router())
-        .nest("/21", day21::world_coord_router())
-        .nest("/22", day22::final_router());
+    // Set the ORDER_STORE secret to "memory" to keep orders and reindeer out of Postgres.
+    let storage = match secrets.get("ORDER_STORE").as_deref() {
+        Some("memory") => Storage::in_memory(),
+        _ => Storage::postgres(pool),
+    };
+
+    // COOKIE_KEY is a base64 key of at least 64 bytes; without one, issued cookies only
+    // verify until a restart. COOKIE_MODE=strict rejects plain base64 recipe cookies.
+    let cookie_key = secrets.get("COOKIE_KEY");
+    if cookie_key.is_none() {
+        tracing::warn!("No COOKIE_KEY secret; issued recipe cookies will not verify after a restart.");
+    }
+    let cookie_mode = secrets.get("COOKIE_MODE");
+    let recipe_cookies = RecipeCookieConfig::from_settings(cookie_key.as_deref(), cookie_mode.as_deref())
+        .map_err(shuttle_runtime::CustomError::msg)?;
+
+    // POKEAPI_URL points day 8 at another PokeAPI server, e.g. a local mirror.
+    let pokeapi = match secrets.get("POKEAPI_URL") {
+        Some(base_url) => PokeApiConfig { base_url: base_url.trim_end_matches('/').into(), ..Default::default() },
+        None => PokeApiConfig::default(),
+    };
+
+    // XOR_CUBE_MAX_NUMBERS and XOR_CUBE_MAX_EXPONENT raise or lower the day 1 limits.
+    let xor_cube = XorCubeConfig::from_settings(
+        secrets.get("XOR_CUBE_MAX_NUMBERS").as_deref(),
+        secrets.get("XOR_CUBE_MAX_EXPONENT").as_deref(),
+    ).map_err(shuttle_runtime::CustomError::msg)?;
+
+    // Shuttle owns the server lifecycle, so sessions are never drained here.
+    let router = build_router(&storage, &WsSessions::new(), xor_cube, recipe_cookies, pokeapi);
 
     Ok(router.into())
-}
+}
\ No newline at end of file
@@ -0,0 +1,84 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header::CONTENT_TYPE, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.body.to_vec()).expect("Response should be UTF-8.")
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).expect("Response should be JSON.")
+    }
+
+    /// The `code` member of a problem document.
+    pub fn error_code(&self) -> String {
+        assert_eq!(self.headers[CONTENT_TYPE], "application/problem+json");
+        self.json()["code"].as_str().unwrap().to_string()
+    }
+}
+
+pub async fn send(router: Router, request: Request<Body>) -> TestResponse {
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    TestResponse { status, headers, body }
+}
+
+pub async fn get(router: Router, uri: &str) -> TestResponse {
+    send(router, Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+pub async fn post(router: Router, uri: &str, content_type: &str, body: impl Into<Body>) -> TestResponse {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.into())
+        .unwrap();
+    send(router, request).await
+}
+
+pub async fn put_json(router: Router, uri: &str, json: Value) -> TestResponse {
+    let request = Request::builder()
+        .method(Method::PUT)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .body(json.to_string().into())
+        .unwrap();
+    send(router, request).await
+}
+
+pub async fn delete(router: Router, uri: &str) -> TestResponse {
+    send(router, Request::delete(uri).body(Body::empty()).unwrap()).await
+}
+
+pub async fn post_text(router: Router, uri: &str, body: &str) -> TestResponse {
+    post(router, uri, "text/plain", body.to_string()).await
+}
+
+pub async fn post_json(router: Router, uri: &str, json: Value) -> TestResponse {
+    post(router, uri, "application/json", json.to_string()).await
+}
+
+/// Serve `router` on an ephemeral local port, for clients that need a real socket.
+pub async fn spawn_server(router: Router) -> SocketAddr {
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+        .serve(router.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    addr
+}
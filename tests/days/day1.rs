@@ -0,0 +1,140 @@
+use axum::Router;
+use cch23_scd91::days::day1::{xor_cube_router, XorCubeConfig};
+use serde_json::{json, Value};
+
+use crate::common::{get, post_json, TestResponse};
+
+fn router() -> Router {
+    xor_cube_router(XorCubeConfig::default())
+}
+
+#[tokio::test]
+async fn xor_cube() {
+    assert_eq!(get(router(), "/4/8").await.text(), "1728");
+    assert_eq!(get(router(), "/10").await.text(), "1000");
+    assert_eq!(get(router(), "/4/5/8/10").await.text(), "27");
+    assert_eq!(get(router(), "/-3/1").await.text(), "-64");
+}
+
+#[tokio::test]
+async fn rejects_bad_input() {
+    let response = get(router(), "/4/eight").await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+
+    let too_many = vec!["1"; 21].join("/");
+    assert_eq!(get(router(), &format!("/{}", too_many)).await.status, 400);
+    assert_eq!(get(router(), "/4/8?op=nand").await.status, 400);
+    assert_eq!(get(router(), "/4/8?exp=65").await.status, 400);
+}
+
+#[tokio::test]
+async fn overflow_is_unprocessable() {
+    let response = get(router(), "/9223372036854775807").await;
+    assert_eq!(response.status, 422);
+    assert_eq!(response.error_code(), "unprocessable_entity");
+    assert_eq!(get(router(), "/9223372036854775807/1?op=sum&exp=1").await.status, 422);
+}
+
+#[tokio::test]
+async fn big_integers_never_overflow() {
+    assert_eq!(
+        get(router(), "/9223372036854775807?big=true").await.text(),
+        "784637716923335095224261902710254454442933591094742482943",
+    );
+    assert_eq!(get(router(), "/-3/1?big=true").await.text(), "-64");
+    assert_eq!(get(router(), "/2?big=true&exp=64").await.text(), "18446744073709551616");
+}
+
+#[tokio::test]
+async fn operators_and_exponents() {
+    assert_eq!(get(router(), "/12/10?op=and").await.text(), "512");
+    assert_eq!(get(router(), "/12/10?op=or&exp=1").await.text(), "14");
+    assert_eq!(get(router(), "/1/2/3?op=sum&exp=2").await.text(), "36");
+    assert_eq!(get(router(), "/2/3/4?op=product&exp=0").await.text(), "1");
+    assert_eq!(get(router(), "/-4/-2?op=and&exp=1&big=true").await.text(), "-4");
+}
+
+#[tokio::test]
+async fn number_limit_is_configurable() {
+    let router = xor_cube_router(XorCubeConfig { max_numbers: 2, ..Default::default() });
+    assert_eq!(get(router.clone(), "/1/2").await.status, 200);
+    assert_eq!(get(router, "/1/2/3").await.status, 400);
+}
+
+async fn eval(expression: &str) -> TestResponse {
+    post_json(router(), "/eval", json!({ "expression": expression })).await
+}
+
+async fn eval_ok(expression: &str) -> Value {
+    let response = eval(expression).await;
+    assert_eq!(response.status, 200, "{}: {}", expression, response.text());
+    response.json()["result"].clone()
+}
+
+#[tokio::test]
+async fn eval_follows_precedence() {
+    assert_eq!(eval_ok("1 + 2 * 3").await, 7);
+    assert_eq!(eval_ok("(1 + 2) * 3").await, 9);
+    assert_eq!(eval_ok("4 ^ 8 ** 1 & 12").await, 12);
+    assert_eq!(eval_ok("2 ** 3 ** 2").await, 512);
+    assert_eq!(eval_ok("-2 ** 2").await, -4);
+    assert_eq!(eval_ok("1 << 4 | 1").await, 17);
+    assert_eq!(eval_ok("-7 / 2 + -7 % 2").await, -4);
+    assert_eq!(eval_ok("cube(4 ^ 8) - max(1, 5, 3) + abs(-2) + pow(2, 10) + min(0)").await, 2749);
+}
+
+#[tokio::test]
+async fn eval_checks_overflow() {
+    for expression in ["9223372036854775807 + 1", "cube(9223372036854775807)", "1 << 63", "1 / 0", "1 << 64"] {
+        let response = eval(expression).await;
+        assert_eq!(response.status, 422, "{}", expression);
+        assert_eq!(response.error_code(), "unprocessable_entity");
+    }
+    assert_eq!(eval_ok("1 ** 9999999999").await, 1);
+}
+
+#[tokio::test]
+async fn parse_errors_carry_the_offset() {
+    for (expression, offset) in [("1 + * 2", 4), ("(1 + 2", 6), ("1 2", 2), ("sqrt(4)", 0), ("2 $ 3", 2), ("max()", 0)] {
+        let response = eval(expression).await;
+        assert_eq!(response.status, 400, "{}", expression);
+        assert_eq!(response.error_code(), "parse_error");
+        assert_eq!(response.json()["offset"], offset, "{}", expression);
+    }
+
+    let nested = format!("{}1{}", "(".repeat(1000), ")".repeat(1000));
+    assert_eq!(eval(&nested).await.error_code(), "parse_error");
+}
+
+#[tokio::test]
+async fn long_chains_are_rejected_before_evaluation() {
+    assert_eq!(eval_ok(&vec!["1"; 500].join("+")).await, 500);
+
+    let chain = vec!["1"; 100_000].join("+");
+    let response = eval(&chain).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "parse_error");
+}
+
+#[tokio::test]
+async fn eval_returns_the_parse_tree_on_request() {
+    assert!(eval("1 + 2").await.json().get("tree").is_none());
+
+    let response = post_json(router(), "/eval", json!({ "expression": "-1 + 2", "tree": true })).await;
+    assert_eq!(response.json(), json!({
+        "result": 1,
+        "tree": {
+            "type": "binary",
+            "offset": 3,
+            "op": "+",
+            "left": {
+                "type": "unary",
+                "offset": 0,
+                "op": "-",
+                "operand": { "type": "number", "offset": 1, "value": 1 },
+            },
+            "right": { "type": "number", "offset": 5, "value": 2 },
+        },
+    }));
+}
\ No newline at end of file
@@ -0,0 +1,383 @@
+use cch23_scd91::days::day11::ornament_router;
+use image::{GrayImage, ImageBuffer, ImageFormat, Rgb, Rgba};
+use serde_json::json;
+
+use crate::{
+    common::{get, post, TestResponse},
+    fixtures::{encode_png, multipart, png, png_rgba},
+};
+
+async fn post_image(uri: &str, data: &[u8]) -> TestResponse {
+    let (content_type, body) = multipart(&[("image", Some("image/png"), data)]);
+    post(ornament_router(), uri, &content_type, body).await
+}
+
+#[tokio::test]
+async fn serves_decoration() {
+    let response = get(ornament_router(), "/assets/decoration.png").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.headers["content-type"], "image/png");
+    assert_eq!(response.body.as_ref(), std::fs::read("assets/decoration.png").unwrap());
+}
+
+#[tokio::test]
+async fn counts_red_pixels() {
+    let image = image::open("assets/decoration.png").unwrap().to_rgb8();
+    let expected = image.pixels()
+        .filter(|p| p[0] as u16 > p[1] as u16 + p[2] as u16)
+        .count();
+
+    let data = std::fs::read("assets/decoration.png").unwrap();
+    let (content_type, body) = multipart(&[("image", Some("image/png"), &data)]);
+    let response = post(ornament_router(), "/red_pixels", &content_type, body).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), expected.to_string());
+}
+
+#[tokio::test]
+async fn counts_generated_png_without_content_type() {
+    let data = png(2, 2, &[[255, 0, 0], [100, 50, 40], [0, 0, 255], [200, 100, 100]]);
+    let (content_type, body) = multipart(&[("image", None, &data)]);
+    let response = post(ornament_router(), "/red_pixels", &content_type, body).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.text(), "2");
+}
+
+#[tokio::test]
+async fn counts_each_image_and_skips_other_fields() {
+    let two = png(2, 1, &[[255, 0, 0], [200, 0, 0]]);
+    let one = png(2, 1, &[[255, 0, 0], [0, 0, 255]]);
+    let (content_type, body) = multipart(&[
+        ("image", None, &two),
+        ("note", Some("application/octet-stream"), b"\xff\xfe not text"),
+        ("image", None, &one),
+    ]);
+    let response = post(ornament_router(), "/red_pixels", &content_type, body).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.text(), "2\r\n1");
+
+    let (content_type, body) = multipart(&[("note", None, b"\xff"), ("image", None, &one)]);
+    let response = post(ornament_router(), "/colors", &content_type, body).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+}
+
+#[tokio::test]
+async fn rejects_invalid_image() {
+    let (content_type, body) = multipart(&[("image", Some("image/png"), b"not a png")]);
+    let response = post(ornament_router(), "/red_pixels", &content_type, body).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "invalid_image");
+}
+
+#[tokio::test]
+async fn applies_alpha_policy() {
+    let data = png_rgba(2, 2, &[[255, 0, 0, 255], [255, 0, 0, 0], [255, 0, 0, 100], [0, 0, 0, 0]]);
+    let count = |uri: &'static str| {
+        let data = data.clone();
+        async move { post_image(uri, &data).await.text() }
+    };
+    assert_eq!(count("/red_pixels").await, "2");
+    assert_eq!(count("/red_pixels?alpha=ignore").await, "2");
+    assert_eq!(count("/red_pixels?alpha=composite").await, "1");
+    assert_eq!(count("/red_pixels?alpha=composite&background=000000").await, "2");
+
+    let response = post_image("/red_pixels?alpha=composite&background=red", &data).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn compares_16_bit_channels_at_full_precision() {
+    // Both pixels round to the same 8-bit color, which is not red.
+    let img = ImageBuffer::from_fn(2, 1, |x, _| Rgb([1000u16, 500, 499 + x as u16]));
+    let response = post_image("/red_pixels", &encode_png(img)).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.text(), "1");
+}
+
+#[tokio::test]
+async fn counts_grayscale_images() {
+    let response = post_image("/red_pixels", &encode_png(GrayImage::new(3, 3))).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.text(), "0");
+}
+
+#[tokio::test]
+async fn classifies_colors() {
+    let data = png(2, 2, &[[255, 0, 0], [0, 200, 0], [255, 215, 0], [128, 128, 128]]);
+    let rules = json!([
+        { "name": "gold", "rule": { "type": "palette", "colors": ["ffd700", "#daa520"], "max_distance": 40 } },
+        { "name": "silver", "rule": { "type": "palette", "colors": ["c0c0c0"] } },
+        { "name": "green", "rule": { "type": "hsv", "hue": [90, 150], "min_saturation": 0.5, "min_value": 0.3 } },
+        { "name": "red", "rule": { "type": "rgb", "all": [{ "r": 1, "g": -1, "b": -1 }] } }
+    ]).to_string();
+    let (content_type, body) = multipart(&[
+        ("image", Some("image/png"), &data),
+        ("rules", None, rules.as_bytes()),
+    ]);
+    let response = post(ornament_router(), "/colors", &content_type, body).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let bucket = |name: &str| json!({ "name": name, "count": 1, "percentage": 25.0 });
+    assert_eq!(response.json(), json!({
+        "total": 4,
+        "buckets": [bucket("gold"), bucket("silver"), bucket("green"), bucket("red")],
+        "unclassified": { "name": "unclassified", "count": 0, "percentage": 0.0 }
+    }));
+}
+
+#[tokio::test]
+async fn classifies_red_by_default() {
+    let data = png_rgba(2, 1, &[[255, 0, 0, 255], [255, 0, 0, 0]]);
+    let response = post_image("/colors", &data).await;
+    assert_eq!(response.json(), json!({
+        "total": 1,
+        "buckets": [{ "name": "red", "count": 1, "percentage": 100.0 }],
+        "unclassified": { "name": "unclassified", "count": 0, "percentage": 0.0 }
+    }));
+}
+
+#[tokio::test]
+async fn rejects_bad_color_rules() {
+    let data = png(1, 1, &[[0, 0, 0]]);
+    for rules in [
+        json!([]),
+        json!([{ "name": "x", "rule": { "type": "cmyk" } }]),
+        json!([{ "name": "x", "rule": { "type": "hsv", "hue": [0, 400] } }]),
+        json!([{ "name": "x", "rule": { "type": "palette", "colors": ["gold"] } }]),
+        json!([{ "name": "x", "rule": { "type": "palette", "colors": [] } }]),
+    ] {
+        let rules = rules.to_string();
+        let (content_type, body) = multipart(&[
+            ("image", Some("image/png"), &data),
+            ("rules", None, rules.as_bytes()),
+        ]);
+        let response = post(ornament_router(), "/colors", &content_type, body).await;
+        assert_eq!(response.status, 400, "{}", rules);
+    }
+
+    let (content_type, body) = multipart(&[("image", None, &data), ("image", None, &data)]);
+    let response = post(ornament_router(), "/colors", &content_type, body).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn analyzes_image() {
+    let data = png(4, 1, &[[255, 0, 0], [255, 0, 0], [0, 0, 255], [0, 255, 0]]);
+    let response = post_image("/analyze?colors=3", &data).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let analysis = response.json();
+    assert_eq!(analysis["width"], 4);
+    assert_eq!(analysis["height"], 1);
+    assert_eq!(analysis["format"], "png");
+    assert_eq!(analysis["color_type"], "rgb8");
+    assert_eq!(analysis["pixels"], 4);
+    assert_eq!(analysis["histogram"]["r"][0], 2);
+    assert_eq!(analysis["histogram"]["r"][255], 2);
+    assert_eq!(analysis["histogram"]["g"].as_array().unwrap().len(), 256);
+    assert_eq!(analysis["mean"], json!([127.5, 63.75, 63.75]));
+    assert_eq!(analysis["median"], json!({ "hex": "000000", "rgb": [0, 0, 0] }));
+    assert_eq!(analysis["dominant"], json!([
+        { "hex": "ff0000", "rgb": [255, 0, 0], "count": 2, "percentage": 50.0 },
+        { "hex": "0000ff", "rgb": [0, 0, 255], "count": 1, "percentage": 25.0 },
+        { "hex": "00ff00", "rgb": [0, 255, 0], "count": 1, "percentage": 25.0 }
+    ]));
+
+    let response = post_image("/analyze?colors=2&quantizer=kmeans", &data).await;
+    assert_eq!(response.json()["dominant"], json!([
+        { "hex": "008080", "rgb": [0, 128, 128], "count": 2, "percentage": 50.0 },
+        { "hex": "ff0000", "rgb": [255, 0, 0], "count": 2, "percentage": 50.0 }
+    ]));
+}
+
+#[tokio::test]
+async fn analyzes_transparent_16_bit_image() {
+    let img = ImageBuffer::from_fn(2, 2, |x, y| Rgba([65535u16, 0, 0, if x == y { 65535 } else { 0 }]));
+    let (content_type, body) = multipart(&[("image", None, &encode_png(img))]);
+    let response = post(ornament_router(), "/analyze", &content_type, body).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let analysis = response.json();
+    assert_eq!(analysis["color_type"], "rgba16");
+    assert_eq!(analysis["pixels"], 2);
+    assert_eq!(analysis["dominant"], json!([
+        { "hex": "ff0000", "rgb": [255, 0, 0], "count": 2, "percentage": 100.0 }
+    ]));
+
+    let response = post_image("/analyze?colors=0", &encode_png(GrayImage::new(1, 1))).await;
+    assert_eq!(response.status, 400);
+}
+
+async fn post_transform(uri: &str, image: Option<&[u8]>, operations: serde_json::Value) -> TestResponse {
+    let operations = operations.to_string();
+    let mut fields = vec![("operations", None, operations.as_bytes())];
+    if let Some(image) = image {
+        fields.push(("image", Some("image/png"), image));
+    }
+    let (content_type, body) = multipart(&fields);
+    post(ornament_router(), uri, &content_type, body).await
+}
+
+#[tokio::test]
+async fn transforms_uploaded_image() {
+    let data = png(3, 2, &[[255, 0, 0], [0, 255, 0], [0, 0, 255], [200, 50, 50], [10, 10, 10], [90, 90, 90]]);
+    let operations = json!([
+        { "op": "crop", "x": 0, "y": 0, "width": 2, "height": 2 },
+        { "op": "recolour_red", "color": "#00ff00" },
+        { "op": "rotate", "degrees": 90 },
+        { "op": "flip", "direction": "vertical" }
+    ]);
+    let response = post_transform("/transform", Some(&data), operations).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.headers["content-type"], "image/png");
+    let result = image::load_from_memory_with_format(&response.body, ImageFormat::Png).unwrap().to_rgb8();
+    assert_eq!(result.dimensions(), (2, 2));
+    // The dark pixel, bottom right in the crop, ends up top left.
+    let pixels: Vec<_> = result.pixels().map(|p| p.0).collect();
+    assert_eq!(pixels, [[10, 10, 10], [0, 255, 0], [0, 255, 0], [0, 255, 0]]);
+}
+
+#[tokio::test]
+async fn transforms_decoration_into_each_format() {
+    let decoration = image::open("assets/decoration.png").unwrap();
+    let operations = json!([
+        { "op": "resize", "width": 64, "height": 64, "mode": "fill" },
+        { "op": "grayscale" },
+        { "op": "blur", "sigma": 1.5 },
+        { "op": "hue_shift", "degrees": 120 }
+    ]);
+    for (uri, content_type, format) in [
+        ("/transform", "image/png", ImageFormat::Png),
+        ("/transform?format=jpeg&quality=60", "image/jpeg", ImageFormat::Jpeg),
+        ("/transform?format=webp", "image/webp", ImageFormat::WebP),
+        ("/transform?format=webp&quality=80", "image/webp", ImageFormat::WebP),
+        ("/transform?format=gif", "image/gif", ImageFormat::Gif),
+    ] {
+        let response = post_transform(uri, None, operations.clone()).await;
+        assert_eq!(response.status, 200, "{}: {}", uri, response.text());
+        assert_eq!(response.headers["content-type"], content_type);
+        let result = image::load_from_memory_with_format(&response.body, format).unwrap();
+        assert_eq!((result.width(), result.height()), (64, 64), "{}", uri);
+    }
+
+    let response = post_transform("/transform", None, json!([])).await;
+    let result = image::load_from_memory(&response.body).unwrap();
+    assert_eq!((result.width(), result.height()), (decoration.width(), decoration.height()));
+}
+
+#[tokio::test]
+async fn rejects_bad_transforms() {
+    let data = png(2, 2, &[[0, 0, 0]; 4]);
+    for (uri, operations) in [
+        ("/transform", json!([{ "op": "resize", "width": 9000, "height": 10 }])),
+        ("/transform", json!([{ "op": "resize", "width": 5000, "height": 5000 }])),
+        ("/transform", json!([{ "op": "resize", "width": 0, "height": 10 }])),
+        ("/transform", json!([{ "op": "crop", "x": 1, "y": 0, "width": 2, "height": 2 }])),
+        ("/transform", json!([{ "op": "rotate", "degrees": 45 }])),
+        ("/transform", json!([{ "op": "blur", "sigma": 0 }])),
+        ("/transform", json!([{ "op": "recolor_red", "color": "green" }])),
+        ("/transform", json!([{ "op": "sharpen" }])),
+        ("/transform?format=png&quality=50", json!([])),
+        ("/transform?format=jpeg&quality=0", json!([])),
+        ("/transform?format=bmp", json!([])),
+    ] {
+        let response = post_transform(uri, Some(&data), operations.clone()).await;
+        assert_eq!(response.status, 400, "{} {}", uri, operations);
+    }
+
+    let (content_type, body) = multipart(&[("image", None, &data), ("image", None, &data)]);
+    let response = post(ornament_router(), "/transform", &content_type, body).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn returns_red_pixel_mask() {
+    let data = png_rgba(3, 1, &[[255, 0, 0, 255], [100, 100, 100, 255], [255, 0, 0, 0]]);
+    let response = post_image("/red_pixels?output=mask", &data).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.headers["content-type"], "image/png");
+    let mask = image::load_from_memory_with_format(&response.body, ImageFormat::Png).unwrap().to_rgba8();
+    let pixels: Vec<_> = mask.pixels().map(|p| p.0).collect();
+    // The transparent red pixel does not count, so it is dimmed like the gray one.
+    assert_eq!(pixels, [[255, 0, 0, 255], [25, 25, 25, 255], [14, 14, 14, 0]]);
+
+    let (content_type, body) = multipart(&[("image", None, &data), ("image", None, &data)]);
+    let response = post(ornament_router(), "/red_pixels?output=mask", &content_type, body).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn finds_red_regions() {
+    const R: [u8; 3] = [255, 0, 0];
+    const W: [u8; 3] = [255, 255, 255];
+    let data = png(5, 3, &[
+        R, R, W, W, R,
+        W, R, W, W, W,
+        W, W, R, W, R,
+    ]);
+    let region = |area: u64, [x, y, width, height]: [u32; 4], cx: f64, cy: f64| json!({
+        "area": area,
+        "bounding_box": { "x": x, "y": y, "width": width, "height": height },
+        "centroid": { "x": cx, "y": cy }
+    });
+
+    let response = post_image("/red_pixels?output=regions", &data).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.json(), json!({
+        "pixels": 6,
+        "bounding_box": { "x": 0, "y": 0, "width": 5, "height": 3 },
+        "region_count": 3,
+        "regions": [
+            region(4, [0, 0, 3, 3], 1.5, 1.25),
+            region(1, [4, 0, 1, 1], 4.5, 0.5),
+            region(1, [4, 2, 1, 1], 4.5, 2.5)
+        ]
+    }));
+
+    let response = post_image("/red_pixels?output=regions&connectivity=4", &data).await;
+    let report = response.json();
+    assert_eq!(report["region_count"], 4);
+    assert_eq!(report["regions"][0]["area"], 3);
+    let centroid = &report["regions"][0]["centroid"];
+    assert!((centroid["x"].as_f64().unwrap() - 7.0 / 6.0).abs() < 1e-9);
+    assert!((centroid["y"].as_f64().unwrap() - 5.0 / 6.0).abs() < 1e-9);
+    assert_eq!(report["regions"][2]["bounding_box"], json!({ "x": 2, "y": 2, "width": 1, "height": 1 }));
+
+    let response = post_image("/red_pixels?output=regions&min_area=2", &data).await;
+    let report = response.json();
+    assert_eq!(report["pixels"], 6);
+    assert_eq!(report["region_count"], 1);
+
+    let response = post_image("/red_pixels?output=regions", &png(1, 1, &[W])).await;
+    assert_eq!(response.json(), json!({ "pixels": 0, "bounding_box": null, "region_count": 0, "regions": [] }));
+
+    let response = post_image("/red_pixels?output=regions&connectivity=6", &data).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn lists_only_the_largest_regions() {
+    const R: [u8; 3] = [255, 0, 0];
+    const W: [u8; 3] = [255, 255, 255];
+    // A red bar along the bottom, below a checkerboard of single red pixels.
+    let (width, height) = (100, 41);
+    let pixels: Vec<_> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| if y == height - 1 || (y % 2 == 0 && x % 2 == 0) { R } else { W }))
+        .collect();
+    let response = post_image("/red_pixels?output=regions&connectivity=4", &png(width, height, &pixels)).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let report = response.json();
+    assert_eq!(report["region_count"], 20 * 50 + 1);
+    let regions = report["regions"].as_array().unwrap();
+    assert_eq!(regions.len(), 1000);
+    assert_eq!(regions[0]["area"], 100);
+    assert_eq!(regions[1]["bounding_box"], json!({ "x": 0, "y": 0, "width": 1, "height": 1 }));
+    assert_eq!(regions[999]["bounding_box"], json!({ "x": 96, "y": 38, "width": 1, "height": 1 }));
+}
+
+#[tokio::test]
+async fn rejects_oversized_uploads_before_decoding() {
+    let data = png(9000, 1, &[[255, 0, 0]; 9000]);
+    for uri in ["/red_pixels", "/red_pixels?output=regions", "/colors", "/analyze", "/transform"] {
+        let response = post_image(uri, &data).await;
+        assert_eq!(response.status, 400, "{}: {}", uri, response.text());
+        assert_eq!(response.error_code(), "invalid_image", "{}", uri);
+    }
+}
\ No newline at end of file
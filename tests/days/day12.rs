@@ -0,0 +1,56 @@
+use cch23_scd91::days::day12::timekeeper_router;
+use serde_json::json;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::common::{get, post_json, post_text};
+
+#[tokio::test]
+async fn save_and_load() {
+    let router = timekeeper_router();
+    assert_eq!(post_text(router.clone(), "/save/packet", "").await.status, 200);
+    let response = get(router.clone(), "/load/packet").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "0");
+
+    let response = get(router, "/load/unknown").await;
+    assert_eq!(response.status, 404);
+    assert_eq!(response.error_code(), "not_found");
+}
+
+#[tokio::test]
+async fn converts_ulids_in_reverse() {
+    let ulids = [Ulid::from_parts(1, 2), Ulid::from_parts(3, 4)];
+    let expected: Vec<String> = ulids.iter().rev()
+        .map(|&u| Uuid::from(u).to_string())
+        .collect();
+    let input: Vec<String> = ulids.iter().map(Ulid::to_string).collect();
+
+    let response = post_json(timekeeper_router(), "/ulids", json!(input)).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!(expected));
+}
+
+#[tokio::test]
+async fn ulid_stats() {
+    // Noon on Sunday, 2023-12-24, UTC.
+    let christmas_eve = Ulid::from_parts(1_703_419_200_000, 1);
+    let other = Ulid::from_parts(1_700_000_000_000, 2);
+    let input = json!([christmas_eve.to_string(), other.to_string()]);
+
+    let response = post_json(timekeeper_router(), "/ulids/6", input).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "christmas eve": 1,
+        "weekday": 1,
+        "in the future": 0,
+        "LSB is 1": 1
+    }));
+}
+
+#[tokio::test]
+async fn invalid_ulid() {
+    let response = post_json(timekeeper_router(), "/ulids", json!(["not a ulid"])).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "invalid_ulid");
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use cch23_scd91::days::{day13::gift_order_router, order_db::MemoryOrderDb};
+use serde_json::json;
+
+use crate::common::{get, post_json};
+
+#[tokio::test]
+async fn sql() {
+    let router = gift_order_router(Arc::new(MemoryOrderDb::new()));
+    assert_eq!(get(router, "/sql").await.text(), "20231213");
+}
+
+#[tokio::test]
+async fn orders_total_and_popular() {
+    let router = gift_order_router(Arc::new(MemoryOrderDb::new()));
+    assert_eq!(post_json(router.clone(), "/reset", json!(null)).await.status, 200);
+    assert_eq!(get(router.clone(), "/orders/popular").await.json(), json!({ "popular": null }));
+
+    let orders = json!([
+        { "id": 1, "region_id": 2, "gift_name": "Toy Train", "quantity": 5 },
+        { "id": 2, "region_id": 2, "gift_name": "Doll", "quantity": 8 },
+        { "id": 3, "region_id": 3, "gift_name": "Toy Train", "quantity": 4 }
+    ]);
+    assert_eq!(post_json(router.clone(), "/orders", orders).await.status, 200);
+    assert_eq!(get(router.clone(), "/orders/total").await.json(), json!({ "total": 17 }));
+    assert_eq!(get(router, "/orders/popular").await.json(), json!({ "popular": "Toy Train" }));
+}
+
+#[tokio::test]
+async fn duplicate_order_conflicts() {
+    let router = gift_order_router(Arc::new(MemoryOrderDb::new()));
+    let order = json!([{ "id": 1, "region_id": 2, "gift_name": "Toy Train", "quantity": 5 }]);
+    assert_eq!(post_json(router.clone(), "/orders", order.clone()).await.status, 200);
+
+    let response = post_json(router.clone(), "/orders", order).await;
+    assert_eq!(response.status, 409);
+    assert_eq!(response.error_code(), "conflict");
+    assert_eq!(get(router, "/orders/total").await.json(), json!({ "total": 5 }));
+}
@@ -0,0 +1,20 @@
+use cch23_scd91::days::day14::html_reindeer_route;
+use serde_json::json;
+
+use crate::common::post_json;
+
+const CONTENT: &str = "<h1>Welcome to the North Pole!</h1>";
+
+#[tokio::test]
+async fn renders_unsafe() {
+    let response = post_json(html_reindeer_route(), "/unsafe", json!({ "content": CONTENT })).await;
+    assert_eq!(response.status, 200);
+    assert!(response.text().contains(CONTENT));
+}
+
+#[tokio::test]
+async fn renders_safe() {
+    let response = post_json(html_reindeer_route(), "/safe", json!({ "content": "<b>'Tom' & \"Jerry\"</b>" })).await;
+    assert_eq!(response.status, 200);
+    assert!(response.text().contains("&lt;b&gt;&apos;Tom&apos; &amp; &quot;Jerry&quot;&lt;/b&gt;"));
+}
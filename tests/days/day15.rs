@@ -0,0 +1,33 @@
+use cch23_scd91::days::day15::nice_password_router;
+use serde_json::json;
+
+use crate::common::post_json;
+
+#[tokio::test]
+async fn nice_and_naughty() {
+    let response = post_json(nice_password_router(), "/nice", json!({ "input": "hello there" })).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({ "result": "nice" }));
+
+    let response = post_json(nice_password_router(), "/nice", json!({ "input": "abcd" })).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.json(), json!({ "result": "naughty" }));
+}
+
+#[tokio::test]
+async fn game_rules() {
+    let cases = [
+        ("mario", 400, "8 chars"),
+        ("mariobro", 400, "more types of chars"),
+        ("EEEEEEEEEEE", 400, "more types of chars"),
+        ("Ee3E3E3E3E3E", 400, "math is hard"),
+        ("e3E3e#eE#ee3#EeE3", 400, "55555"),
+        ("2000.23.A j  ;) o  ;) y", 451, "illegal: no sandwich"),
+        ("2020.3.A y  ;) o  ;) j", 406, "not joyful enough"),
+    ];
+    for (input, status, reason) in cases {
+        let response = post_json(nice_password_router(), "/game", json!({ "input": input })).await;
+        assert_eq!(response.status, status, "{}", input);
+        assert_eq!(response.json(), json!({ "result": "naughty", "reason": reason }), "{}", input);
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use cch23_scd91::days::{day18::gift_order_router2, order_db::MemoryOrderDb};
+use serde_json::json;
+
+use crate::common::{get, post_json};
+
+async fn seeded_router() -> axum::Router {
+    let router = gift_order_router2(Arc::new(MemoryOrderDb::new()));
+    assert_eq!(post_json(router.clone(), "/reset", json!(null)).await.status, 200);
+
+    let regions = json!([
+        { "id": 1, "name": "North Pole" },
+        { "id": 2, "name": "Europe" },
+        { "id": 3, "name": "North America" },
+        { "id": 4, "name": "South America" },
+        { "id": 5, "name": "Africa" }
+    ]);
+    assert_eq!(post_json(router.clone(), "/regions", regions).await.status, 200);
+
+    let orders = json!([
+        { "id": 1, "region_id": 2, "gift_name": "Board Game", "quantity": 5 },
+        { "id": 2, "region_id": 2, "gift_name": "Origami Set", "quantity": 8 },
+        { "id": 3, "region_id": 3, "gift_name": "Action Figure", "quantity": 12 },
+        { "id": 4, "region_id": 4, "gift_name": "Teddy Bear", "quantity": 10 },
+        { "id": 5, "region_id": 2, "gift_name": "Yarn Ball", "quantity": 6 },
+        { "id": 6, "region_id": 3, "gift_name": "Art Set", "quantity": 3 },
+        { "id": 7, "region_id": 5, "gift_name": "Robot Lego Kit", "quantity": 5 },
+        { "id": 8, "region_id": 6, "gift_name": "Drone", "quantity": 9 }
+    ]);
+    assert_eq!(post_json(router.clone(), "/orders", orders).await.status, 200);
+    router
+}
+
+#[tokio::test]
+async fn totals_by_region() {
+    let response = get(seeded_router().await, "/regions/total").await;
+    assert_eq!(response.json(), json!([
+        { "region": "Africa", "total": 5 },
+        { "region": "Europe", "total": 19 },
+        { "region": "North America", "total": 15 },
+        { "region": "South America", "total": 10 }
+    ]));
+}
+
+#[tokio::test]
+async fn top_list() {
+    let response = get(seeded_router().await, "/regions/top_list/2").await;
+    assert_eq!(response.json(), json!([
+        { "region": "Africa", "top_gifts": ["Robot Lego Kit"] },
+        { "region": "Europe", "top_gifts": ["Origami Set", "Yarn Ball"] },
+        { "region": "North America", "top_gifts": ["Action Figure", "Art Set"] },
+        { "region": "North Pole", "top_gifts": [] },
+        { "region": "South America", "top_gifts": ["Teddy Bear"] }
+    ]));
+
+    let response = get(seeded_router().await, "/regions/top_list/-1").await;
+    assert_eq!(response.status, 400);
+}
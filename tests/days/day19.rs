@@ -0,0 +1,80 @@
+use std::{net::SocketAddr, time::Duration};
+
+use cch23_scd91::days::day19::{ws_games_router, WsSessions};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::common::spawn_server;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn connect(addr: SocketAddr, path: &str) -> Socket {
+    let (socket, _) = connect_async(format!("ws://{}{}", addr, path)).await.unwrap();
+    socket
+}
+
+async fn next_text(socket: &mut Socket) -> String {
+    let msg = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("Timed out waiting for message.")
+        .unwrap()
+        .unwrap();
+    msg.into_text().unwrap()
+}
+
+#[tokio::test]
+async fn ping_pong() {
+    let addr = spawn_server(ws_games_router(WsSessions::new())).await;
+    let mut socket = connect(addr, "/ws/ping").await;
+
+    // Pings before the game starts are ignored.
+    socket.send(Message::Text("ping".into())).await.unwrap();
+    socket.send(Message::Text("serve".into())).await.unwrap();
+    socket.send(Message::Text("ping".into())).await.unwrap();
+    assert_eq!(next_text(&mut socket).await, "pong");
+}
+
+#[tokio::test]
+async fn chat_room_broadcasts_and_counts_views() {
+    let addr = spawn_server(ws_games_router(WsSessions::new())).await;
+    let client = reqwest::Client::new();
+    client.post(format!("http://{}/reset", addr)).send().await.unwrap();
+
+    let mut alice = connect(addr, "/ws/room/1/user/alice").await;
+    let mut bob = connect(addr, "/ws/room/1/user/bob").await;
+    // Give both sessions time to subscribe to the room.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    alice.send(Message::Text(json!({ "message": "Hello, Bob" }).to_string())).await.unwrap();
+    let expected = json!({ "user": "alice", "message": "Hello, Bob" });
+    for socket in [&mut alice, &mut bob] {
+        let msg: Value = serde_json::from_str(&next_text(socket).await).unwrap();
+        assert_eq!(msg, expected);
+    }
+
+    // Over-long messages are dropped.
+    alice.send(Message::Text(json!({ "message": "x".repeat(129) }).to_string())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let views = client.get(format!("http://{}/views", addr)).send().await.unwrap().text().await.unwrap();
+    assert_eq!(views, "2");
+}
+
+#[tokio::test]
+async fn drain_closes_sessions() {
+    let sessions = WsSessions::new();
+    let addr = spawn_server(ws_games_router(sessions.clone())).await;
+    let mut ping = connect(addr, "/ws/ping").await;
+    let mut chat = connect(addr, "/ws/room/2/user/carol").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(sessions.active(), 2);
+
+    tokio::time::timeout(Duration::from_secs(5), sessions.drain()).await.unwrap();
+    assert_eq!(sessions.active(), 0);
+    for socket in [&mut ping, &mut chat] {
+        let msg = socket.next().await.unwrap().unwrap();
+        assert!(matches!(msg, Message::Close(_)));
+    }
+}
@@ -0,0 +1,40 @@
+use cch23_scd91::days::day20::archive_router;
+
+use crate::{
+    common::post,
+    fixtures::{git_tar, git_tar_cookie_commit},
+};
+
+const TAR: &str = "application/x-tar";
+
+#[tokio::test]
+async fn counts_files() {
+    let response = post(archive_router(), "/archive_files", TAR, git_tar()).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "10");
+}
+
+#[tokio::test]
+async fn sums_file_sizes() {
+    let tar_data = git_tar();
+    let expected: u64 = tar::Archive::new(tar_data.as_slice()).entries().unwrap()
+        .map(|e| e.unwrap().size())
+        .sum();
+    let response = post(archive_router(), "/archive_files_size", TAR, tar_data).await;
+    assert_eq!(response.text(), expected.to_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn finds_cookie() {
+    let response = post(archive_router(), "/cookie", TAR, git_tar()).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let (author, commit) = git_tar_cookie_commit();
+    assert_eq!(response.text(), format!("{} {}", author, commit));
+}
+
+#[tokio::test]
+async fn rejects_other_content_types() {
+    let response = post(archive_router(), "/archive_files", "application/json", "[]").await;
+    assert_eq!(response.status, 415);
+    assert_eq!(response.error_code(), "unsupported_media_type");
+}
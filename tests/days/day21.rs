@@ -0,0 +1,27 @@
+use cch23_scd91::days::day21::world_coord_router;
+
+use crate::{common::get, stand_ins};
+
+#[tokio::test]
+async fn coords() {
+    let router = world_coord_router("http://127.0.0.1:9");
+    let response = get(router, "/coords/0100111110010011000110011001010101011111000010100011110001011011").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "83°39'54.324''N 30°37'40.584''W");
+}
+
+#[tokio::test]
+async fn country() {
+    let photon = stand_ins::photon().await;
+    let router = world_coord_router(&photon.base_url);
+    let response = get(router, "/country/0010000111110000011111100000111010111100000100111101111011000101").await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.text(), "Brazil");
+    assert_eq!(photon.hits(), 1);
+}
+
+#[tokio::test]
+async fn bad_cell_id() {
+    let response = get(world_coord_router("http://127.0.0.1:9"), "/coords/012").await;
+    assert_eq!(response.status, 400);
+}
@@ -0,0 +1,36 @@
+use cch23_scd91::days::day22::final_router;
+
+use crate::common::post_text;
+
+#[tokio::test]
+async fn finds_only_integer() {
+    let response = post_text(final_router(), "/integers", "888\n77\n888\n22\n77\n").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "🎁".repeat(22));
+}
+
+#[tokio::test]
+async fn rocket_path() {
+    let input = "5
+0 1 0
+-2 2 3
+3 -3 -5
+1 1 5
+4 3 5
+4
+0 1
+2 4
+3 4
+1 2
+";
+    let response = post_text(final_router(), "/rocket", input).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "3 26.123");
+}
+
+#[tokio::test]
+async fn rocket_rejects_unknown_star() {
+    let response = post_text(final_router(), "/rocket", "1\n0 0 0\n1\n0 7\n").await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+}
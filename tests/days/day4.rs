@@ -0,0 +1,317 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::Router;
+use cch23_scd91::days::{
+    day4::serdeer_router,
+    reindeer_db::{MemoryReindeerDb, ReindeerDb, MAX_MEMORY_CONTESTS},
+};
+use serde_json::json;
+
+use crate::common::{delete, get, post, post_json, put_json};
+
+fn router() -> Router {
+    serdeer_router(Arc::new(MemoryReindeerDb::new()))
+}
+
+#[tokio::test]
+async fn strength() {
+    let reindeer = json!([
+        { "name": "Dasher", "strength": 5 },
+        { "name": "Dancer", "strength": 6 },
+        { "name": "Prancer", "strength": 4 },
+        { "name": "Vixen", "strength": 7 }
+    ]);
+    let response = post_json(router(), "/strength", reindeer).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "22");
+}
+
+#[tokio::test]
+async fn contest() {
+    let reindeer = json!([
+        {
+            "name": "Dasher", "strength": 5, "speed": 50.4, "height": 80, "antler_width": 36,
+            "snow_magic_power": 9001, "favorite_food": "hay", "cAnD13s_3ATeN-yesT3rdAy": 2
+        },
+        {
+            "name": "Dancer", "strength": 6, "speed": 48.2, "height": 65, "antler_width": 37,
+            "snow_magic_power": 4004, "favorite_food": "grass", "cAnD13s_3ATeN-yesT3rdAy": 5
+        }
+    ]);
+    let response = post_json(router(), "/contest", reindeer).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "fastest": "Speeding past the finish line with a strength of 5 is Dasher",
+        "tallest": "Dasher is standing tall with his 36 cm wide antlers",
+        "magician": "Dasher could blast you away with a snow magic power of 9001",
+        "consumer": "Dancer ate lots of candies, but also some grass"
+    }));
+}
+
+#[tokio::test]
+async fn empty_contest_is_rejected() {
+    let response = post_json(router(), "/contest", json!([])).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+}
+
+
+#[tokio::test]
+async fn negative_measurements_are_rejected() {
+    let response = post_json(router(), "/strength", json!([{ "name": "Dasher", "strength": -5 }])).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+}
+
+#[tokio::test]
+async fn reindeer_crud() {
+    let router = router();
+    let dasher = json!({ "name": "Dasher", "strength": 5, "speed": 50.4, "favorite_food": "hay" });
+    let response = post_json(router.clone(), "/reindeer", dasher).await;
+    assert_eq!(response.status, 201);
+    let id = response.json()["id"].as_i64().unwrap();
+
+    let stored = get(router.clone(), &format!("/reindeer/{}", id)).await.json();
+    assert_eq!(stored["name"], "Dasher");
+    assert_eq!(stored["speed"], 50.4);
+    assert_eq!(stored["cAnD13s_3ATeN-yesT3rdAy"], 0);
+
+    let updated = put_json(router.clone(), &format!("/reindeer/{}", id), json!({ "name": "Dasher", "strength": 7 })).await;
+    assert_eq!(updated.json()["strength"], 7);
+    assert_eq!(get(router.clone(), "/reindeer").await.json().as_array().unwrap().len(), 1);
+
+    assert_eq!(delete(router.clone(), &format!("/reindeer/{}", id)).await.status, 204);
+    let response = get(router.clone(), &format!("/reindeer/{}", id)).await;
+    assert_eq!(response.status, 404);
+    assert_eq!(response.error_code(), "not_found");
+    assert_eq!(delete(router.clone(), &format!("/reindeer/{}", id)).await.status, 404);
+    assert_eq!(put_json(router, "/reindeer/99", json!({ "name": "Comet", "strength": 1 })).await.status, 404);
+}
+
+#[tokio::test]
+async fn contests_are_recorded() {
+    let router = router();
+    assert_eq!(post_json(router.clone(), "/contest/stored", json!(null)).await.status, 400);
+
+    for reindeer in [
+        json!({ "name": "Dasher", "strength": 5, "speed": 50.4, "height": 80, "favorite_food": "hay" }),
+        json!({ "name": "Dancer", "strength": 6, "speed": 48.2, "height": 90, "favorite_food": "grass",
+                "snow_magic_power": 10, "cAnD13s_3ATeN-yesT3rdAy": 5 }),
+    ] {
+        post_json(router.clone(), "/reindeer", reindeer).await;
+    }
+    let response = post_json(router.clone(), "/contest/stored", json!(null)).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["fastest"], "Speeding past the finish line with a strength of 5 is Dasher");
+
+    let prancer = json!([{ "name": "Prancer", "strength": 1, "favorite_food": "moss" }]);
+    post_json(router.clone(), "/contest", prancer).await;
+
+    let history = get(router.clone(), "/contest/history").await.json();
+    assert_eq!(history.as_array().unwrap().len(), 2);
+    assert_eq!(history[0]["winners"]["consumer"], "Prancer");
+    assert_eq!(history[1]["winners"], json!({
+        "fastest": "Dasher", "tallest": "Dancer", "magician": "Dancer", "consumer": "Dancer"
+    }));
+    assert_eq!(get(router.clone(), "/contest/history?limit=1").await.json().as_array().unwrap().len(), 1);
+    assert_eq!(get(router, "/contest/history?limit=-1").await.status, 400);
+}
+
+#[tokio::test]
+async fn memory_contest_history_is_limited() {
+    let db = MemoryReindeerDb::new();
+    let winners = BTreeMap::from([("fastest".to_string(), "Dasher".to_string())]);
+    for _ in 0..MAX_MEMORY_CONTESTS + 5 {
+        db.record_contest(&winners).await.unwrap();
+    }
+
+    let history = db.contest_history(i64::MAX).await.unwrap();
+    assert_eq!(history.len(), MAX_MEMORY_CONTESTS);
+    // The oldest are dropped, and their ids are not reused.
+    assert_eq!(history[0].id, MAX_MEMORY_CONTESTS as i64 + 5);
+    assert_eq!(history[MAX_MEMORY_CONTESTS - 1].id, 6);
+}
+
+#[tokio::test]
+async fn ranked_contest_with_custom_categories() {
+    let spec = json!({
+        "categories": [{
+            "name": "smallest",
+            "field": "height",
+            "order": "ascending",
+            "tie_break": [{ "field": "name", "order": "ascending" }],
+            "template": "#{place} {name} at {value} cm"
+        }, {
+            "name": "strongest",
+            "field": "strength",
+            "template": "{name}"
+        }],
+        "reindeer": [
+            { "name": "Vixen", "strength": 3, "height": 70 },
+            { "name": "Comet", "strength": 3, "height": 70 },
+            { "name": "Cupid", "strength": 1, "height": 60 }
+        ]
+    });
+    let rankings = post_json(router(), "/contest/ranked", spec).await.json();
+    assert_eq!(rankings[0]["category"], "smallest");
+    assert_eq!(rankings[0]["places"], json!([
+        { "place": 1, "name": "Cupid", "value": 60, "message": "#1 Cupid at 60 cm" },
+        { "place": 2, "name": "Comet", "value": 70, "message": "#2 Comet at 70 cm" },
+        { "place": 3, "name": "Vixen", "value": 70, "message": "#3 Vixen at 70 cm" }
+    ]));
+
+    // Without a tie-break, Vixen and Comet share first place.
+    let places: Vec<_> = rankings[1]["places"].as_array().unwrap().iter()
+        .map(|p| (p["place"].as_u64().unwrap(), p["name"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(places, [(1, "Vixen".into()), (1, "Comet".into()), (3, "Cupid".into())]);
+}
+
+#[tokio::test]
+async fn ranked_contest_defaults_to_the_registry_and_original_categories() {
+    let router = router();
+    for name in ["Dasher", "Dancer"] {
+        post_json(router.clone(), "/reindeer", json!({ "name": name, "strength": 1, "favorite_food": "hay" })).await;
+    }
+    let rankings = post_json(router.clone(), "/contest/ranked", json!({})).await.json();
+    let categories: Vec<_> = rankings.as_array().unwrap().iter().map(|r| r["category"].clone()).collect();
+    assert_eq!(categories, ["fastest", "tallest", "magician", "consumer"]);
+    assert_eq!(rankings[3]["places"][0]["message"], "Dancer ate lots of candies, but also some hay");
+    assert_eq!(rankings[3]["places"][1]["place"], 2);
+
+    let history = get(router, "/contest/history").await.json();
+    assert_eq!(history[0]["winners"]["fastest"], "Dancer");
+}
+
+#[tokio::test]
+async fn ranked_contest_rejects_bad_specs() {
+    let reindeer = json!([{ "name": "Dasher", "strength": 1 }]);
+    for categories in [
+        json!([{ "name": "fastest", "field": "speed", "template": "{nickname}" }]),
+        json!([{ "name": "fastest", "field": "speed", "template": "{name" }]),
+        json!([]),
+        json!([
+            { "name": "fastest", "field": "speed", "template": "{name}" },
+            { "name": "fastest", "field": "height", "template": "{name}" }
+        ]),
+    ] {
+        let spec = json!({ "categories": categories, "reindeer": reindeer });
+        let response = post_json(router(), "/contest/ranked", spec).await;
+        assert_eq!(response.status, 400, "{}", categories);
+        assert_eq!(response.error_code(), "bad_request");
+    }
+}
+
+#[tokio::test]
+async fn strength_from_csv_and_ndjson() {
+    let csv = "strength,name,speed\n5,Dasher,50.4\n6,Dancer,48.2\n";
+    assert_eq!(post(router(), "/strength", "text/csv", csv).await.text(), "11");
+
+    let ndjson = "{\"name\":\"Dasher\",\"strength\":5}\n\n{\"name\":\"Dancer\",\"strength\":6}";
+    assert_eq!(post(router(), "/strength", "application/x-ndjson", ndjson).await.text(), "11");
+}
+
+#[tokio::test]
+async fn contest_from_csv_maps_headers() {
+    let csv = "name,strength,speed,height,antler_width,snow_magic_power,favorite_food,cAnD13s_3ATeN-yesT3rdAy\n\
+        Dasher,5,50.4,80,36,9001,hay,2\n\
+        Dancer,6,48.2,65,37,4004,grass,5\n";
+    let response = post(router(), "/contest", "text/csv; charset=utf-8", csv).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    assert_eq!(response.json()["consumer"], "Dancer ate lots of candies, but also some grass");
+    assert_eq!(response.json()["magician"], "Dasher could blast you away with a snow magic power of 9001");
+}
+
+#[tokio::test]
+async fn contest_herds_are_limited() {
+    let csv = format!("name,strength\n{}", "Dasher,5\n".repeat(100_001));
+    let response = post(router(), "/contest", "text/csv", csv).await;
+    assert_eq!(response.status, 413);
+    assert_eq!(response.error_code(), "payload_too_large");
+
+    // JSON is read whole, so it is held to a body limit instead.
+    let herd = format!("[{}]", vec![r#"{"name":"Dasher","strength":5}"#; 100_000].join(","));
+    let response = post(router(), "/contest", "application/json", herd).await;
+    assert_eq!(response.status, 413);
+    assert_eq!(response.error_code(), "payload_too_large");
+}
+
+#[tokio::test]
+async fn bad_rows_report_row_and_column() {
+    let csv = "name,strength,speed\nDasher,5,50.4\nDancer,six,48.2\n";
+    let response = post(router(), "/strength", "text/csv", csv).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "invalid_record");
+    assert_eq!((response.json()["row"].clone(), response.json()["column"].clone()), (json!(3), json!(2)));
+
+    let ndjson = "{\"name\":\"Dasher\",\"strength\":5}\n{\"name\":\"Dancer\",\"strength\":-6}\n";
+    let response = post(router(), "/strength", "application/x-ndjson", ndjson).await;
+    assert_eq!(response.error_code(), "invalid_record");
+    assert_eq!(response.json()["row"], 2);
+
+    let response = post(router(), "/strength", "text/plain", "Dasher").await;
+    assert_eq!(response.status, 415);
+}
+
+#[tokio::test]
+async fn herd_stats() {
+    let herd = json!([
+        { "name": "Dasher", "strength": 1, "speed": 10.0, "favorite_food": "hay" },
+        { "name": "Dancer", "strength": 2, "speed": 10.0, "favorite_food": "grass" },
+        { "name": "Prancer", "strength": 3, "speed": 10.0, "favorite_food": "hay" },
+        { "name": "Vixen", "strength": 4, "speed": 10.0 },
+        { "name": "Comet", "strength": 10, "speed": 10.0, "favorite_food": "moss" }
+    ]);
+    let response = post_json(router(), "/stats?buckets=3&percentiles=0,25,100", herd).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    let stats = response.json();
+    assert_eq!(stats["count"], 5);
+
+    let strength = &stats["fields"]["strength"];
+    assert_eq!(strength["min"], 1.0);
+    assert_eq!(strength["max"], 10.0);
+    assert_eq!(strength["mean"], 4.0);
+    assert_eq!(strength["median"], 3.0);
+    assert!((strength["std_dev"].as_f64().unwrap() - 10.0f64.sqrt()).abs() < 1e-9);
+    assert_eq!(strength["percentiles"], json!({ "0": 1.0, "25": 2.0, "100": 10.0 }));
+    let counts: Vec<_> = strength["histogram"].as_array().unwrap().iter().map(|b| b["count"].clone()).collect();
+    assert_eq!(counts, [json!(3), json!(1), json!(1)]);
+
+    // Every reindeer is equally fast, so there is a single bucket.
+    assert_eq!(stats["fields"]["speed"]["histogram"], json!([{ "start": 10.0, "end": 10.0, "count": 5 }]));
+    assert_eq!(stats["fields"]["speed"]["std_dev"], 0.0);
+
+    assert_eq!(stats["favorite_foods"], json!([
+        { "food": "hay", "count": 2 },
+        { "food": "grass", "count": 1 },
+        { "food": "moss", "count": 1 }
+    ]));
+}
+
+#[tokio::test]
+async fn herd_stats_from_csv_with_defaults() {
+    let csv = "name,strength,candies_eaten_yesterday\nDasher,5,3\nDancer,7,1\n";
+    let stats = post(router(), "/stats", "text/csv", csv).await.json();
+    assert_eq!(stats["fields"]["candies_eaten_yesterday"]["mean"], 2.0);
+    assert_eq!(stats["fields"]["strength"]["histogram"].as_array().unwrap().len(), 10);
+    assert_eq!(stats["fields"]["strength"]["percentiles"]["99"], 6.98);
+}
+
+#[tokio::test]
+async fn herd_stats_herds_are_limited() {
+    let ndjson = "{\"name\":\"Dasher\",\"strength\":5}\n".repeat(100_001);
+    let response = post(router(), "/stats", "application/x-ndjson", ndjson).await;
+    assert_eq!(response.status, 413);
+    assert_eq!(response.error_code(), "payload_too_large");
+}
+
+#[tokio::test]
+async fn herd_stats_rejects_bad_parameters() {
+    let herd = json!([{ "name": "Dasher", "strength": 1 }]);
+    for uri in ["/stats?buckets=0", "/stats?percentiles=101", "/stats?percentiles=median"] {
+        let response = post_json(router(), uri, herd.clone()).await;
+        assert_eq!(response.status, 400, "{}", uri);
+        assert_eq!(response.error_code(), "bad_request");
+    }
+    assert_eq!(post_json(router(), "/stats", json!([])).await.status, 400);
+}
\ No newline at end of file
@@ -0,0 +1,169 @@
+use std::io::Write;
+
+use axum::{
+    body::Body,
+    http::{header::{CONTENT_ENCODING, CONTENT_TYPE}, Request},
+};
+use cch23_scd91::days::day6::elf_router;
+use flate2::{write::GzEncoder, Compression};
+use serde_json::json;
+
+use crate::common::{post, post_json, post_text, send};
+
+#[tokio::test]
+async fn counts_elves() {
+    let text = "The mischievous elf peeked out from behind the toy workshop,
+      and another elf joined in the festive dance.
+      Look, there is also an elf on that shelf!";
+    let response = post_text(elf_router(), "/", text).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["elf"], 4);
+}
+
+#[tokio::test]
+async fn counts_shelves() {
+    let text = "there is an elf on a shelf on an elf.
+      there is also another shelf in Belfast.";
+    let response = post_text(elf_router(), "/", text).await;
+    assert_eq!(response.json(), json!({
+        "elf": 5,
+        "elf on a shelf": 1,
+        "shelf with no elf on it": 1
+    }));
+}
+
+#[tokio::test]
+async fn count_patterns_with_context_rules() {
+    let request = json!({
+        "text": "there is an elf on a shelf on an elf. there is also another shelf in Belfast.",
+        "patterns": [
+            "elf",
+            { "pattern": "shelf", "name": "elf on a shelf", "preceded_by": "elf on a " },
+            { "pattern": "shelf", "name": "lonely shelf", "not_preceded_by": "elf on a " }
+        ]
+    });
+    let response = post_json(elf_router(), "/count", request).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({ "elf": 5, "elf on a shelf": 1, "lonely shelf": 1 }));
+}
+
+#[tokio::test]
+async fn count_options() {
+    let count = |options: serde_json::Value| {
+        let mut request = json!({ "text": "Aaaa aa, aA!", "patterns": ["aa"] });
+        request.as_object_mut().unwrap().extend(options.as_object().unwrap().clone());
+        async move { post_json(elf_router(), "/count", request).await.json()["aa"].clone() }
+    };
+    assert_eq!(count(json!({})).await, 2);
+    assert_eq!(count(json!({ "overlapping": true })).await, 3);
+    assert_eq!(count(json!({ "case_insensitive": true })).await, 4);
+    assert_eq!(count(json!({ "case_insensitive": true, "overlapping": true })).await, 5);
+    assert_eq!(count(json!({ "whole_word": true })).await, 1);
+    assert_eq!(count(json!({ "whole_word": true, "case_insensitive": true })).await, 2);
+}
+
+#[tokio::test]
+async fn count_rejects_bad_patterns() {
+    for patterns in [json!([]), json!([""]), json!(["elf", { "pattern": "shelf", "name": "elf" }])] {
+        let response = post_json(elf_router(), "/count", json!({ "text": "elf", "patterns": patterns })).await;
+        assert_eq!(response.status, 400, "{}", patterns);
+        assert_eq!(response.error_code(), "bad_request");
+    }
+}
+
+#[tokio::test]
+async fn counts_across_chunk_boundaries() {
+    // Splits "elf on a shelf" mid-word and a multibyte character between its bytes.
+    let chunks: Vec<Vec<u8>> = vec![
+        b"an elf on a sh".to_vec(),
+        b"elf, a \xc3".to_vec(),
+        b"\xa9lf, and a shelf".to_vec(),
+        b" on an e".to_vec(),
+        b"lf".to_vec(),
+    ];
+    let body = Body::wrap_stream(futures::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>)));
+    let request = Request::post("/").header(CONTENT_TYPE, "text/plain").body(body).unwrap();
+    let response = send(elf_router(), request).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "elf": 4,
+        "elf on a shelf": 1,
+        "shelf with no elf on it": 1
+    }));
+}
+
+#[tokio::test]
+async fn counts_gzip_bodies() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all("there is an elf on a shelf on an elf.".repeat(1000).as_bytes()).unwrap();
+    let request = Request::post("/")
+        .header(CONTENT_TYPE, "text/plain")
+        .header(CONTENT_ENCODING, "gzip")
+        .body(Body::from(encoder.finish().unwrap()))
+        .unwrap();
+    let response = send(elf_router(), request).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "elf": 3000,
+        "elf on a shelf": 1000,
+        "shelf with no elf on it": 0
+    }));
+}
+
+#[tokio::test]
+async fn rejects_truncated_utf8() {
+    let response = post(elf_router(), "/", "text/plain", b"an elf \xc3".to_vec()).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn reports_match_positions() {
+    let response = post_text(elf_router(), "/?view=matches", "é elf on a shelf").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "counts": { "elf": 2, "elf on a shelf": 1, "shelf with no elf on it": 0 },
+        "matches": [
+            { "category": "elf", "bytes": { "start": 3, "end": 6 }, "chars": { "start": 2, "end": 5 } },
+            { "category": "elf on a shelf", "bytes": { "start": 12, "end": 17 }, "chars": { "start": 11, "end": 16 } },
+            { "category": "elf", "bytes": { "start": 14, "end": 17 }, "chars": { "start": 13, "end": 16 } }
+        ]
+    }));
+}
+
+#[tokio::test]
+async fn listing_matches_is_limited_to_small_bodies() {
+    let text = "elf ".repeat(600_000);
+    assert_eq!(post_text(elf_router(), "/", &text).await.json()["elf"], 600_000);
+    for view in ["matches", "html"] {
+        let response = post_text(elf_router(), &format!("/?view={}", view), &text).await;
+        assert_eq!(response.status, 413, "{}", view);
+        assert_eq!(response.error_code(), "payload_too_large");
+    }
+}
+
+#[tokio::test]
+async fn highlights_matches() {
+    let text = "<b>elf</b> on a shelf, & a shelf";
+    let response = post_text(elf_router(), "/?view=html", text).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.json()["highlighted"],
+        "&lt;b&gt;<mark class=\"elf\">elf</mark>&lt;/b&gt; on a \
+            <mark class=\"shelf-with-no-elf\">sh<mark class=\"elf\">elf</mark></mark>, &amp; a \
+            <mark class=\"shelf-with-no-elf\">sh<mark class=\"elf\">elf</mark></mark>"
+    );
+
+    let response = post_text(elf_router(), "/?view=ansi", "elf on a shelf").await;
+    assert_eq!(
+        response.json()["highlighted"],
+        "\x1b[32melf\x1b[0m on a \x1b[31msh\x1b[32melf\x1b[0m\x1b[0m"
+    );
+    // Only the highlighting reaches the terminal as escape codes.
+    let response = post_text(elf_router(), "/?view=ansi", "\x1b[2J\relf\tin\n\u{9b}31m").await;
+    assert_eq!(response.json()["highlighted"], "\u{241b}[2J\u{240d}\x1b[32melf\x1b[0m\tin\n\u{fffd}31m");
+    assert_eq!(post_text(elf_router(), "/", "elf").await.json(), json!({
+        "elf": 1,
+        "elf on a shelf": 0,
+        "shelf with no elf on it": 0
+    }));
+}
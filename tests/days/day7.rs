@@ -0,0 +1,386 @@
+use axum::{
+    body::Body,
+    http::{header::{CONTENT_TYPE, COOKIE, SET_COOKIE}, Request},
+    Router,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use cch23_scd91::days::day7::{cookie_router, RecipeCookieConfig};
+use serde_json::json;
+
+use crate::{common::{post_json, send, TestResponse}, fixtures::recipe_cookie};
+
+fn router_with_mode(mode: &str) -> Router {
+    let key = BASE64_STANDARD.encode([7u8; 64]);
+    cookie_router(RecipeCookieConfig::from_settings(Some(&key), Some(mode)).unwrap())
+}
+
+fn router() -> Router {
+    router_with_mode("compat")
+}
+
+// The cookie set by `/issue`.
+async fn issue(router: Router, kind: &str, recipe: serde_json::Value) -> String {
+    let response = post_json(router, &format!("/issue?kind={}", kind), recipe).await;
+    assert_eq!(response.status, 204);
+    set_cookie(&response)
+}
+
+// The `name=value` part of a response's `Set-Cookie` header.
+fn set_cookie(response: &TestResponse) -> String {
+    response.headers[SET_COOKIE].to_str().unwrap().split(';').next().unwrap().to_string()
+}
+
+fn get_with_cookie(uri: &str, cookie: &str) -> Request<Body> {
+    Request::get(uri)
+        .header(COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn decode() {
+    let recipe = json!({ "flour": 100, "chocolate chips": 20 });
+    let response = send(router(), get_with_cookie("/decode", &recipe_cookie(&recipe))).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), recipe);
+}
+
+#[tokio::test]
+async fn bake() {
+    let input = json!({
+        "recipe": { "flour": 95, "sugar": 50, "butter": 30, "baking powder": 10, "chocolate chips": 50 },
+        "pantry": { "flour": 385, "sugar": 507, "butter": 2122, "baking powder": 865, "chocolate chips": 457 }
+    });
+    let response = send(router(), get_with_cookie("/bake", &recipe_cookie(&input))).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "cookies": 4,
+        "pantry": { "flour": 5, "sugar": 307, "butter": 2002, "baking powder": 825, "chocolate chips": 257 }
+    }));
+}
+
+#[tokio::test]
+async fn bake_without_ingredient() {
+    let input = json!({
+        "recipe": { "flour": 95, "slime": 1 },
+        "pantry": { "flour": 385 }
+    });
+    let response = send(router(), get_with_cookie("/bake", &recipe_cookie(&input))).await;
+    assert_eq!(response.json()["cookies"], 0);
+}
+
+#[tokio::test]
+async fn missing_or_invalid_cookie() {
+    let response = send(router(), Request::get("/decode").body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+
+    let response = send(router(), get_with_cookie("/decode", "recipe=not*base64")).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn issued_cookies_verify() {
+    let input = json!({ "recipe": { "flour": 10 }, "pantry": { "flour": 35 } });
+    let router = router_with_mode("strict");
+    for kind in ["signed", "private"] {
+        let cookie = issue(router.clone(), kind, input.clone()).await;
+        let response = send(router.clone(), get_with_cookie("/decode", &cookie)).await;
+        assert_eq!(response.status, 200, "{}", kind);
+        assert_eq!(response.json(), input);
+
+        let response = send(router.clone(), get_with_cookie("/bake", &cookie)).await;
+        assert_eq!(response.json(), json!({ "cookies": 3, "pantry": { "flour": 5 } }));
+    }
+
+    // A private cookie hides the recipe from the client.
+    let cookie = issue(router.clone(), "private", input.clone()).await;
+    assert!(!cookie.contains(&BASE64_STANDARD.encode(input.to_string())));
+}
+
+#[tokio::test]
+async fn rejects_tampered_cookies() {
+    let router = router_with_mode("strict");
+    let cookie = issue(router.clone(), "signed", json!({ "flour": 10 })).await;
+    // Signed values carry their plain base64 payload after the signature.
+    let forged = cookie.replace(
+        &BASE64_STANDARD.encode(json!({ "flour": 10 }).to_string()),
+        &BASE64_STANDARD.encode(json!({ "flour": 99 }).to_string()),
+    );
+    assert_ne!(forged, cookie);
+
+    for cookie in [forged, recipe_cookie(&json!({ "flour": 99 }))] {
+        let response = send(router.clone(), get_with_cookie("/decode", &cookie)).await;
+        assert_eq!(response.status, 400);
+        assert_eq!(response.error_code(), "invalid_signature");
+    }
+
+    // Cookies from a server with another key do not verify either.
+    let other = cookie_router(RecipeCookieConfig::from_settings(None, Some("strict")).unwrap());
+    let response = send(other, get_with_cookie("/decode", &cookie)).await;
+    assert_eq!(response.error_code(), "invalid_signature");
+}
+
+#[tokio::test]
+async fn rejects_tampered_cookies_in_compat_mode() {
+    let router = router();
+    let signed = issue(router.clone(), "signed", json!({ "flour": 10 })).await;
+    let forged = signed.replace(
+        &BASE64_STANDARD.encode(json!({ "flour": 10 }).to_string()),
+        &BASE64_STANDARD.encode(json!({ "flour": 99 }).to_string()),
+    );
+    let private = issue(router.clone(), "private", json!({ "flour": 10 })).await;
+    // Flip a character in the middle of the encrypted value.
+    let mid = private.len() / 2;
+    let flipped = if &private[mid..mid + 1] == "A" { "B" } else { "A" };
+    let corrupted = format!("{}{}{}", &private[..mid], flipped, &private[mid + 1..]);
+
+    for cookie in [forged, corrupted] {
+        let response = send(router.clone(), get_with_cookie("/decode", &cookie)).await;
+        assert_eq!(response.status, 400, "{}", cookie);
+        assert_eq!(response.error_code(), "invalid_signature", "{}", cookie);
+    }
+
+    // Plain cookies are still accepted.
+    let plain = recipe_cookie(&json!({ "flour": 99 }));
+    assert_eq!(send(router, get_with_cookie("/decode", &plain)).await.json(), json!({ "flour": 99 }));
+}
+
+#[test]
+fn cookie_config_validation() {
+    assert!(RecipeCookieConfig::from_settings(Some("not*base64"), None).is_err());
+    assert!(RecipeCookieConfig::from_settings(Some(&BASE64_STANDARD.encode([0u8; 32])), None).is_err());
+    assert!(RecipeCookieConfig::from_settings(None, Some("lenient")).is_err());
+    assert!(RecipeCookieConfig::from_settings(None, None).unwrap().accept_plain);
+}
+
+#[tokio::test]
+async fn bake_sets_cookie_for_next_bake() {
+    let input = json!({ "recipe": { "flour": 10, "sugar": 5 }, "pantry": { "flour": 25, "sugar": 100 } });
+    let router = router();
+    let signed = issue(router.clone(), "signed", input.clone()).await;
+    for (cookie, plain) in [(recipe_cookie(&input), true), (signed, false)] {
+        let response = send(router.clone(), get_with_cookie("/bake?set_cookie=true", &cookie)).await;
+        assert_eq!(response.json()["cookies"], 2);
+        let next = set_cookie(&response);
+
+        // The new cookie keeps the encoding of the old one.
+        // Set-Cookie values are percent-encoded, which here only touches base64 padding.
+        let value = next.strip_prefix("recipe=").unwrap().replace("%3D", "=");
+        assert_eq!(BASE64_STANDARD.decode(value).is_ok(), plain);
+        let response = send(router_with_mode("strict"), get_with_cookie("/decode", &next)).await;
+        assert_eq!(response.status == 200, !plain);
+
+        let response = send(router.clone(), get_with_cookie("/decode", &next)).await;
+        assert_eq!(response.json()["pantry"], json!({ "flour": 5, "sugar": 90 }));
+        let response = send(router.clone(), get_with_cookie("/bake", &next)).await;
+        assert_eq!(response.json()["cookies"], 0);
+        assert!(!response.headers.contains_key(SET_COOKIE));
+    }
+}
+
+#[tokio::test]
+async fn restock_pantry() {
+    let router = router_with_mode("strict");
+    let cookie = issue(router.clone(), "private", json!({
+        "recipe": { "flour": 10, "eggs": 1 },
+        "pantry": { "flour": 5 }
+    })).await;
+    let request = Request::post("/pantry")
+        .header(COOKIE, &cookie)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "flour": 20, "eggs": 3 }).to_string()))
+        .unwrap();
+    let response = send(router.clone(), request).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["pantry"], json!({ "flour": 25, "eggs": 3 }));
+
+    let next = set_cookie(&response);
+    let response = send(router, get_with_cookie("/bake", &next)).await;
+    assert_eq!(response.json(), json!({ "cookies": 2, "pantry": { "flour": 5, "eggs": 1 } }));
+}
+
+#[tokio::test]
+async fn bake_with_units() {
+    let input = json!({
+        "recipe": {
+            "flour": { "amount": 250, "unit": "g" },
+            "milk": { "amount": 1, "unit": "cup" },
+            "eggs": 1,
+            "sugar": { "amount": 2, "unit": "tbsp" }
+        },
+        "pantry": {
+            "flour": { "amount": 1.1, "unit": "kg" },
+            "milk": { "amount": 1, "unit": "l" },
+            "eggs": 12,
+            "sugar": { "amount": 0.5, "unit": "cups" }
+        }
+    });
+    let response = send(router(), get_with_cookie("/bake", &recipe_cookie(&input))).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json(), json!({
+        "cookies": 4,
+        "pantry": {
+            "flour": { "amount": 0.1, "unit": "kg" },
+            "milk": { "amount": 0.053647054, "unit": "l" },
+            "eggs": 8,
+            "sugar": { "amount": 0, "unit": "cups" }
+        }
+    }));
+}
+
+#[tokio::test]
+async fn bake_counts_large_pantries_exactly() {
+    let bake = |recipe: serde_json::Value, pantry: serde_json::Value| async move {
+        let input = json!({ "recipe": recipe, "pantry": pantry });
+        send(router(), get_with_cookie("/bake", &recipe_cookie(&input))).await.json()
+    };
+    assert_eq!(
+        bake(json!({ "flour": 1 }), json!({ "flour": 1_000_000_000u64 })).await,
+        json!({ "cookies": 1_000_000_000u64, "pantry": { "flour": 0 } }),
+    );
+    // Past 2^53, where an f64 no longer holds every integer.
+    assert_eq!(
+        bake(json!({ "flour": 1 }), json!({ "flour": 9_007_199_254_740_993u64 })).await,
+        json!({ "cookies": 9_007_199_254_740_993u64, "pantry": { "flour": 0 } }),
+    );
+    let cookies = 4_503_599_627_370_496u64;
+    assert_eq!(
+        bake(json!({ "flour": 2, "eggs": 1 }), json!({ "flour": 9_007_199_254_740_993u64, "eggs": u64::MAX })).await,
+        json!({ "cookies": cookies, "pantry": { "flour": 1, "eggs": u64::MAX - cookies } }),
+    );
+    // Measured amounts are not exact, but a large one is still not overcounted.
+    let grams = |amount: f64| json!({ "flour": { "amount": amount, "unit": "g" } });
+    assert_eq!(
+        bake(grams(1.0), grams(1e9)).await,
+        json!({ "cookies": 1_000_000_000u64, "pantry": { "flour": { "amount": 0, "unit": "g" } } }),
+    );
+}
+
+#[tokio::test]
+async fn bake_rejects_incompatible_units() {
+    let input = json!({
+        "recipe": { "flour": { "amount": 250, "unit": "g" } },
+        "pantry": { "flour": { "amount": 2, "unit": "cups" } }
+    });
+    let response = send(router(), get_with_cookie("/bake", &recipe_cookie(&input))).await;
+    assert_eq!(response.status, 422);
+    assert_eq!(response.error_code(), "unprocessable_entity");
+
+    let input = json!({ "recipe": { "flour": -1 }, "pantry": { "flour": 2 } });
+    let response = send(router(), get_with_cookie("/bake", &recipe_cookie(&input))).await;
+    assert_eq!(response.status, 400);
+}
+
+#[tokio::test]
+async fn restock_in_other_units() {
+    let input = json!({
+        "recipe": { "flour": { "amount": 100, "unit": "g" } },
+        "pantry": { "flour": { "amount": 1, "unit": "kg" } }
+    });
+    let request = Request::post("/pantry")
+        .header(COOKIE, recipe_cookie(&input))
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({
+            "flour": { "amount": 500, "unit": "g" },
+            "vanilla": { "amount": 1.5, "unit": "tbsp" }
+        }).to_string()))
+        .unwrap();
+    let response = send(router(), request).await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.json()["pantry"], json!({
+        "flour": { "amount": 1.5, "unit": "kg" },
+        "vanilla": { "amount": 1.5, "unit": "tbsp" }
+    }));
+}
+
+#[tokio::test]
+async fn plan_maximizes_value() {
+    let g = |amount: u64| json!({ "amount": amount, "unit": "g" });
+    let request = json!({
+        "recipes": {
+            "chocolate chip": { "ingredients": { "flour": g(100), "butter": g(50), "chips": g(30) }, "value": 3 },
+            "shortbread": { "ingredients": { "flour": g(150), "butter": g(100) }, "value": 4 },
+            "meringue": { "ingredients": { "eggs": 2, "sugar": g(50) }, "value": 2 }
+        },
+        "pantry": {
+            "flour": { "amount": 1, "unit": "kg" },
+            "butter": g(500),
+            "chips": g(200),
+            "eggs": 5,
+            "sugar": { "amount": 1, "unit": "kg" }
+        }
+    });
+    let response = post_json(router(), "/plan", request).await;
+    assert_eq!(response.status, 200);
+    // Baking shortbread first, as the most valuable, would only reach 24.
+    assert_eq!(response.json(), json!({
+        "plan": { "chocolate chip": 6, "meringue": 2, "shortbread": 2 },
+        "value": 30.0,
+        "optimal": true,
+        "pantry": {
+            "flour": { "amount": 0.1, "unit": "kg" },
+            "butter": g(0),
+            "chips": g(20),
+            "eggs": 1,
+            "sugar": { "amount": 0.9, "unit": "kg" }
+        },
+        "limiting_ingredients": ["butter", "chips", "eggs", "flour"]
+    }));
+}
+
+#[tokio::test]
+async fn plan_prefers_priority_on_ties() {
+    let request = json!({
+        "recipes": {
+            "a": { "ingredients": { "flour": 1 } },
+            "b": { "ingredients": { "flour": 1 }, "priority": 1 },
+            "c": { "ingredients": { "flour": 1, "magic": 1 }, "value": 10 }
+        },
+        "pantry": { "flour": 3 }
+    });
+    let response = post_json(router(), "/plan", request).await;
+    assert_eq!(response.json()["plan"], json!({ "a": 0, "b": 3, "c": 0 }));
+    assert_eq!(response.json()["limiting_ingredients"], json!(["flour", "magic"]));
+    assert_eq!(response.json()["pantry"], json!({ "flour": 0 }));
+}
+
+#[tokio::test]
+async fn plan_does_not_overdraw_large_pantries() {
+    let request = json!({
+        "recipes": { "a": { "ingredients": { "flour": 1 } } },
+        "pantry": { "flour": 1_000_000_000u64 }
+    });
+    let response = post_json(router(), "/plan", request).await;
+    assert_eq!(response.json()["plan"], json!({ "a": 1_000_000_000u64 }));
+    assert_eq!(response.json()["pantry"], json!({ "flour": 0 }));
+
+    let request = json!({
+        "recipes": { "a": { "ingredients": { "flour": { "amount": 1, "unit": "g" } } } },
+        "pantry": { "flour": { "amount": 1_000_000, "unit": "kg" } }
+    });
+    let response = post_json(router(), "/plan", request).await;
+    assert_eq!(response.json()["plan"], json!({ "a": 1_000_000_000u64 }));
+    assert_eq!(response.json()["pantry"], json!({ "flour": { "amount": 0, "unit": "kg" } }));
+}
+
+#[tokio::test]
+async fn plan_rejects_bad_recipes() {
+    for recipes in [
+        json!({}),
+        json!({ "air": { "ingredients": {} } }),
+        json!({ "a": { "ingredients": { "flour": 1 }, "value": -1 } }),
+        json!({ "a": { "ingredients": { "flour": { "amount": 1, "unit": "ml" } } } }),
+    ] {
+        let request = json!({ "recipes": recipes, "pantry": { "flour": { "amount": 1, "unit": "g" } } });
+        let response = post_json(router(), "/plan", request).await;
+        assert!(response.status.is_client_error(), "{}", recipes);
+    }
+
+    // One more ingredient than allowed, counting the flour in the pantry.
+    let spices: serde_json::Value = (0..64).map(|i| (format!("spice {}", i), json!(1))).collect();
+    let request = json!({ "recipes": { "a": { "ingredients": spices } }, "pantry": { "flour": 1 } });
+    let response = post_json(router(), "/plan", request).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+}
\ No newline at end of file
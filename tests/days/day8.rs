@@ -0,0 +1,220 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::Body,
+    http::{header::ACCEPT, Request},
+    Router,
+};
+use cch23_scd91::days::day8::{pokemon_router, PokeApi, PokeApiConfig};
+use serde_json::json;
+
+use crate::{common::{get, post_json, send}, stand_ins};
+
+fn router_for(base_url: &str, config: PokeApiConfig) -> Router {
+    let source = PokeApi::new(PokeApiConfig { base_url: base_url.into(), ..config }).unwrap();
+    pokemon_router(Arc::new(source))
+}
+
+#[tokio::test]
+async fn weight() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    assert_eq!(get(router.clone(), "/weight/25").await.text(), "6");
+    assert_eq!(get(router, "/weight/1").await.text(), "6.9");
+}
+
+#[tokio::test]
+async fn drop_momentum() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let response = get(router, "/drop/25").await;
+    assert_eq!(response.status, 200);
+    let momentum: f64 = response.text().parse().unwrap();
+    assert!((momentum - 84.10707461325713).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn upstream_failure_is_bad_gateway() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let response = get(router, "/weight/9999").await;
+    assert_eq!(response.status, 502);
+    assert_eq!(response.error_code(), "upstream_error");
+    // The upstream URL stays in the server logs.
+    assert_eq!(response.json()["detail"], "An upstream service failed");
+}
+
+#[tokio::test]
+async fn repeated_lookups_are_cached() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    get(router.clone(), "/weight/25").await;
+    get(router.clone(), "/drop/25").await;
+    get(router.clone(), "/weight/25").await;
+    assert_eq!(pokeapi.hits(), 1);
+
+    get(router, "/weight/1").await;
+    assert_eq!(pokeapi.hits(), 2);
+}
+
+#[tokio::test]
+async fn concurrent_lookups_share_one_fetch() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let responses = futures::future::join_all((0..5).map(|_| get(router.clone(), "/weight/25"))).await;
+    assert!(responses.iter().all(|response| response.text() == "6"));
+    assert_eq!(pokeapi.hits(), 1);
+
+    // Failures are not shared, but they still fail every waiter.
+    let responses = futures::future::join_all((0..3).map(|_| get(router.clone(), "/weight/9999"))).await;
+    assert!(responses.iter().all(|response| response.status == 502));
+}
+
+#[tokio::test]
+async fn expired_entries_are_fetched_again() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let config = PokeApiConfig { cache_ttl: Duration::ZERO, ..Default::default() };
+    let router = router_for(&pokeapi.base_url, config);
+    get(router.clone(), "/weight/25").await;
+    get(router, "/weight/25").await;
+    assert_eq!(pokeapi.hits(), 2);
+}
+
+#[tokio::test]
+async fn slow_upstream_times_out() {
+    // Accept connections, but never answer.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let config = PokeApiConfig { timeout: Duration::from_millis(200), ..Default::default() };
+    let response = get(router_for(&base_url, config), "/weight/25").await;
+    assert_eq!(response.status, 502);
+}
+
+async fn drop_json(uri: &str) -> serde_json::Value {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let request = Request::get(uri)
+        .header(ACCEPT, "application/json")
+        .body(Body::empty())
+        .unwrap();
+    let response = send(router, request).await;
+    assert_eq!(response.status, 200, "{}", response.text());
+    response.json()
+}
+
+#[tokio::test]
+async fn drop_json_reports_all_quantities() {
+    let result = drop_json("/drop/25").await;
+    let speed = result["impact_speed"].as_f64().unwrap();
+    assert!((speed - (2.0f64 * 9.825 * 10.0).sqrt()).abs() < 1e-9);
+    assert!((result["momentum"].as_f64().unwrap() - 6.0 * speed).abs() < 1e-9);
+    assert!((result["kinetic_energy"].as_f64().unwrap() - 3.0 * speed * speed).abs() < 1e-6);
+    assert!((result["time_to_impact"].as_f64().unwrap() - (20.0f64 / 9.825).sqrt()).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn drop_view_overrides_accept() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let full = get(router.clone(), "/drop/25?view=full").await;
+    assert_eq!(full.headers["content-type"], "application/json");
+    assert_eq!(full.json(), drop_json("/drop/25").await);
+
+    let request = Request::get("/drop/25?view=momentum")
+        .header(ACCEPT, "application/json")
+        .body(Body::empty())
+        .unwrap();
+    let momentum = send(router.clone(), request).await;
+    assert_eq!(momentum.text().parse::<f64>().unwrap(), full.json()["momentum"].as_f64().unwrap());
+
+    let response = get(router, "/drop/25?view=table").await;
+    assert_eq!(response.status, 400);
+    assert_eq!(response.error_code(), "bad_request");
+}
+
+#[tokio::test]
+async fn drop_on_the_moon() {
+    let result = drop_json("/drop/25?gravity=moon&height=2").await;
+    assert!((result["impact_speed"].as_f64().unwrap() - (2.0f64 * 1.62 * 2.0).sqrt()).abs() < 1e-9);
+
+    // There is no air on the moon, so drag changes nothing.
+    assert_eq!(drop_json("/drop/25?gravity=moon&height=2&drag=true").await, result);
+}
+
+#[tokio::test]
+async fn drag_slows_the_fall() {
+    let vacuum = drop_json("/drop/25?gravity=earth&height=1000").await;
+    let air = drop_json("/drop/25?gravity=earth&height=1000&drag=true").await;
+    let air_speed = air["impact_speed"].as_f64().unwrap();
+    assert!(air_speed < vacuum["impact_speed"].as_f64().unwrap());
+    assert!(air["time_to_impact"].as_f64().unwrap() > vacuum["time_to_impact"].as_f64().unwrap());
+
+    // From this height, Pikachu is at terminal velocity: sqrt(m g / (0.5 rho Cd A)).
+    let area = std::f64::consts::PI * 0.2f64.powi(2);
+    let terminal = (6.0 * 9.80665 / (0.5 * 1.225 * 0.47 * area)).sqrt();
+    assert!((air_speed - terminal).abs() < 1e-3);
+}
+
+#[tokio::test]
+async fn drop_rejects_bad_parameters() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    for uri in ["/drop/25?gravity=pluto", "/drop/25?gravity=-1", "/drop/25?height=0"] {
+        let response = get(router.clone(), uri).await;
+        assert_eq!(response.status, 400, "{}", uri);
+        assert_eq!(response.error_code(), "bad_request");
+    }
+}
+
+#[tokio::test]
+async fn batch_reports_each_pokemon() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let response = post_json(router, "/batch", json!([25, "Snorlax", 9999, "not/a-name"])).await;
+    assert_eq!(response.status, 200);
+    let results = response.json()["results"].clone();
+
+    assert_eq!(results[0]["query"], 25);
+    assert_eq!(results[0]["name"], "pikachu");
+    assert_eq!(results[0]["weight"], 6.0);
+    assert!((results[0]["momentum"].as_f64().unwrap() - 84.10707461325713).abs() < 1e-9);
+    assert_eq!(results[1]["id"], 143);
+    assert_eq!(results[2]["error"]["status"], 502);
+    assert_eq!(results[2]["error"]["code"], "upstream_error");
+    assert_eq!(results[2]["error"]["detail"], "An upstream service failed");
+    assert_eq!(results[3]["error"]["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn batch_shares_the_cache_between_ids_and_names() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    post_json(router.clone(), "/batch", json!([25])).await;
+    post_json(router, "/batch", json!(["pikachu", "25"])).await;
+    assert_eq!(pokeapi.hits(), 1);
+}
+
+#[tokio::test]
+async fn batch_rejects_oversized_requests() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let response = post_json(router, "/batch", json!(vec![25; 101])).await;
+    assert_eq!(response.status, 400);
+    assert_eq!(pokeapi.hits(), 0);
+}
+
+#[tokio::test]
+async fn compare() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let router = router_for(&pokeapi.base_url, PokeApiConfig::default());
+    let result = get(router.clone(), "/compare/pikachu/143").await.json();
+    assert_eq!(result["heavier"], "snorlax");
+    assert!((result["ratio"].as_f64().unwrap() - 4600.0 / 60.0).abs() < 1e-9);
+    assert_eq!(result["pokemon"][0]["name"], "pikachu");
+
+    let same = get(router.clone(), "/compare/25/pikachu").await.json();
+    assert_eq!(same["heavier"], serde_json::Value::Null);
+    assert_eq!(same["ratio"], 1.0);
+
+    assert_eq!(get(router, "/compare/25/9999").await.status, 502);
+}
\ No newline at end of file
@@ -0,0 +1,145 @@
+//! Request bodies for the routers that take binary or encoded input.
+use std::io::{Cursor, Write};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use flate2::{write::ZlibEncoder, Compression};
+use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use serde_json::Value;
+
+pub const MULTIPART_BOUNDARY: &str = "cch23-test-boundary";
+
+/// A `recipe` cookie header value holding the base64 encoded JSON.
+pub fn recipe_cookie(recipe: &Value) -> String {
+    format!("recipe={}", BASE64_STANDARD.encode(recipe.to_string()))
+}
+
+/// Encode an image of any pixel format as a PNG.
+pub fn encode_png(img: impl Into<DynamicImage>) -> Vec<u8> {
+    let mut out = Cursor::new(Vec::new());
+    img.into().write_to(&mut out, ImageOutputFormat::Png).unwrap();
+    out.into_inner()
+}
+
+/// Encode `pixels` (row-major RGB) as a PNG.
+pub fn png(width: u32, height: u32, pixels: &[[u8; 3]]) -> Vec<u8> {
+    let mut img = RgbImage::new(width, height);
+    for (p, &rgb) in img.pixels_mut().zip(pixels) {
+        *p = Rgb(rgb);
+    }
+    encode_png(img)
+}
+
+/// Encode `pixels` (row-major RGBA) as a PNG with an alpha channel.
+pub fn png_rgba(width: u32, height: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+    let mut img = RgbaImage::new(width, height);
+    for (p, &rgba) in img.pixels_mut().zip(pixels) {
+        *p = Rgba(rgba);
+    }
+    encode_png(img)
+}
+
+/// A multipart/form-data body, from `(name, content type, data)` fields.
+pub fn multipart(fields: &[(&str, Option<&str>, &[u8])]) -> (String, Vec<u8>) {
+    let mut body = Vec::new();
+    for (name, content_type, data) in fields {
+        write!(body, "--{}\r\n", MULTIPART_BOUNDARY).unwrap();
+        write!(body, "Content-Disposition: form-data; name=\"{}\"; filename=\"{}.bin\"\r\n", name, name).unwrap();
+        if let Some(content_type) = content_type {
+            write!(body, "Content-Type: {}\r\n", content_type).unwrap();
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    write!(body, "--{}--\r\n", MULTIPART_BOUNDARY).unwrap();
+    (format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY), body)
+}
+
+// Git object ids are only used as names here, so derive them from a seed instead of hashing.
+fn object_id(seed: u8) -> [u8; 20] {
+    [seed; 20]
+}
+
+fn hex(id: &[u8; 20]) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn git_object(kind: &str, content: &[u8]) -> Vec<u8> {
+    let mut obj = format!("{} {}\0", kind, content.len()).into_bytes();
+    obj.extend_from_slice(content);
+    zlib(&obj)
+}
+
+fn git_tree(entries: &[(&str, &str, [u8; 20])]) -> Vec<u8> {
+    let mut content = Vec::new();
+    for (mode, name, id) in entries {
+        write!(content, "{} {}\0", mode, name).unwrap();
+        content.extend_from_slice(id);
+    }
+    git_object("tree", &content)
+}
+
+fn git_commit(tree: [u8; 20], parent: Option<[u8; 20]>, author: &str) -> Vec<u8> {
+    let mut content = format!("tree {}\n", hex(&tree));
+    if let Some(parent) = parent {
+        content.push_str(&format!("parent {}\n", hex(&parent)));
+    }
+    content.push_str(&format!(
+        "author {} <{}@example.com> 1703000000 +0000\ncommitter {} <{}@example.com> 1703000000 +0000\n\nGifts\n",
+        author, author, author, author
+    ));
+    git_object("commit", content.as_bytes())
+}
+
+fn append_file(builder: &mut tar::Builder<Vec<u8>>, path: &str, data: &[u8]) {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data).unwrap();
+}
+
+/// Author and commit id of the commit with the cookie in [`git_tar`].
+pub fn git_tar_cookie_commit() -> (&'static str, String) {
+    ("Grinch", hex(&object_id(10)))
+}
+
+/// A tar with a `.git` directory, where the `christmas` branch has two commits.
+///
+/// The head commit has a `santa.txt` without a cookie. Its parent, by "Grinch",
+/// has one under `presents/santa.txt`.
+pub fn git_tar() -> Vec<u8> {
+    let plain_blob = object_id(1);
+    let cookie_blob = object_id(2);
+    let presents_tree = object_id(3);
+    let old_root_tree = object_id(4);
+    let new_root_tree = object_id(5);
+    let old_commit = object_id(10);
+    let new_commit = object_id(11);
+
+    let objects = vec![
+        (plain_blob, git_object("blob", b"Milk and carrots")),
+        (cookie_blob, git_object("blob", b"A COOKIE for Santa")),
+        (presents_tree, git_tree(&[("100644", "santa.txt", cookie_blob)])),
+        (old_root_tree, git_tree(&[("40000", "presents", presents_tree)])),
+        (new_root_tree, git_tree(&[("100644", "santa.txt", plain_blob)])),
+        (old_commit, git_commit(old_root_tree, None, "Grinch")),
+        (new_commit, git_commit(new_root_tree, Some(old_commit), "Santa")),
+    ];
+
+    let mut builder = tar::Builder::new(Vec::new());
+    append_file(&mut builder, "README.md", b"Not in git\n");
+    append_file(&mut builder, ".git/HEAD", b"ref: refs/heads/christmas\n");
+    append_file(&mut builder, ".git/refs/heads/christmas", format!("{}\n", hex(&new_commit)).as_bytes());
+    for (id, data) in objects {
+        let name = hex(&id);
+        append_file(&mut builder, &format!(".git/objects/{}/{}", &name[..2], &name[2..]), &data);
+    }
+    builder.into_inner().unwrap()
+}
@@ -0,0 +1,25 @@
+//! End-to-end tests for every day router, driven through `tower::ServiceExt::oneshot`.
+//!
+//! Routers with upstream calls are pointed at local stand-in servers, so the suite runs offline.
+
+mod common;
+mod fixtures;
+mod stand_ins;
+
+mod day1;
+mod day4;
+mod day6;
+mod day7;
+mod day8;
+mod day11;
+mod day12;
+mod day13;
+mod day14;
+mod day15;
+mod day18;
+mod day19;
+mod day20;
+mod day21;
+mod day22;
+mod order_db;
+mod root;
@@ -0,0 +1,87 @@
+//! The same checks against every `OrderDb` backend, so the in-memory one stays a faithful
+//! stand-in for Postgres.
+//!
+//! The Postgres run connects to `TEST_DATABASE_URL`, dropping its `orders` and `regions` tables,
+//! and is skipped when that is not set.
+use cch23_scd91::days::order_db::{MemoryOrderDb, Order, OrderDb, PgOrderDb, Region};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn order(id: i32, region_id: i32, gift_name: &str, quantity: i32) -> Order {
+    Order { id, region_id, gift_name: gift_name.into(), quantity }
+}
+
+fn to_json(value: impl Serialize) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+
+async fn check_conformance(db: &dyn OrderDb) {
+    db.reset_orders_and_regions().await.unwrap();
+    assert_eq!(db.ping().await.unwrap(), 20231213);
+    assert_eq!(db.total_quantity().await.unwrap(), 0);
+    assert_eq!(db.most_popular_gift().await.unwrap(), None);
+
+    // Names that sort differently by bytes than by most locales, and two regions named alike.
+    let regions = [(1, "b"), (2, "B"), (3, "a"), (4, "a"), (5, "Empty")]
+        .map(|(id, name)| Region { id, name: name.into() });
+    db.insert_regions(&regions).await.unwrap();
+    db.insert_orders(&[
+        order(1, 1, "Yo-yo", 5),
+        order(2, 1, "Kite", 5),
+        order(3, 1, "kite", 5),
+        order(4, 1, "Drum", 2),
+        order(5, 3, "Ball", 4),
+        order(6, 4, "Ball", 1),
+        order(7, 9, "Yo-yo", 4), // No such region.
+        order(8, 3, "Kite", 4),
+    ]).await.unwrap();
+
+    assert_eq!(db.total_quantity().await.unwrap(), 30);
+    // "Kite" and "Yo-yo" tie at 9.
+    assert_eq!(db.most_popular_gift().await.unwrap().as_deref(), Some("Kite"));
+    assert_eq!(to_json(db.totals_by_region().await.unwrap()), json!([
+        { "region": "a", "total": 8 },
+        { "region": "a", "total": 1 },
+        { "region": "b", "total": 17 }
+    ]));
+    assert_eq!(to_json(db.top_gifts_by_region(2).await.unwrap()), json!([
+        { "region": "B", "top_gifts": [] },
+        { "region": "Empty", "top_gifts": [] },
+        { "region": "a", "top_gifts": ["Ball", "Kite"] },
+        { "region": "a", "top_gifts": ["Ball"] },
+        { "region": "b", "top_gifts": ["Kite", "Yo-yo"] }
+    ]));
+    let no_gifts = to_json(db.top_gifts_by_region(0).await.unwrap());
+    assert!(no_gifts.as_array().unwrap().iter().all(|r| r["top_gifts"] == json!([])));
+    let error = db.top_gifts_by_region(-1).await.err().unwrap();
+    assert_eq!((error.status().as_u16(), error.code()), (400, "bad_request"));
+
+    // A duplicate id anywhere in the batch inserts nothing.
+    let error = db.insert_orders(&[order(9, 1, "Drum", 1), order(1, 1, "Drum", 1)]).await.err().unwrap();
+    assert_eq!(error.code(), "conflict");
+    assert_eq!(db.total_quantity().await.unwrap(), 30);
+    let error = db.insert_regions(&[Region { id: 2, name: "C".into() }]).await.err().unwrap();
+    assert_eq!(error.code(), "conflict");
+
+    // Resetting orders keeps the regions.
+    db.reset_orders().await.unwrap();
+    assert_eq!(db.total_quantity().await.unwrap(), 0);
+    assert_eq!(to_json(db.totals_by_region().await.unwrap()), json!([]));
+    assert_eq!(db.top_gifts_by_region(1).await.unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn memory_backend_conforms() {
+    check_conformance(&MemoryOrderDb::new()).await;
+}
+
+#[tokio::test]
+async fn postgres_backend_conforms() {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("Skipping: TEST_DATABASE_URL is not set.");
+        return;
+    };
+    let pool = PgPool::connect(&url).await.expect("TEST_DATABASE_URL should be reachable.");
+    check_conformance(&PgOrderDb::new(pool)).await;
+}
\ No newline at end of file
@@ -0,0 +1,73 @@
+use axum::Router;
+use cch23_scd91::{
+    build_router,
+    days::{day1::XorCubeConfig, day19::WsSessions, day7::RecipeCookieConfig, day8::PokeApiConfig},
+    Storage,
+};
+
+use serde_json::json;
+
+use crate::{common::{get, post, post_json}, stand_ins};
+
+fn router_with(xor_cube: XorCubeConfig, pokeapi: PokeApiConfig) -> Router {
+    build_router(&Storage::in_memory(), &WsSessions::new(), xor_cube, RecipeCookieConfig::default(), pokeapi)
+}
+
+fn router() -> Router {
+    router_with(XorCubeConfig::default(), PokeApiConfig::default())
+}
+
+#[tokio::test]
+async fn hello_world() {
+    let router = router();
+    let response = get(router, "/").await;
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "Hello, Santa!");
+}
+
+#[tokio::test]
+async fn fake_error() {
+    let router = router();
+    assert_eq!(get(router, "/-1/error").await.status, 500);
+}
+
+#[tokio::test]
+async fn pokeapi_config_is_used() {
+    let pokeapi = stand_ins::pokeapi().await;
+    let config = PokeApiConfig { base_url: pokeapi.base_url.clone(), ..Default::default() };
+    let router = router_with(XorCubeConfig::default(), config);
+    assert_eq!(get(router, "/8/weight/25").await.text(), "6");
+    assert_eq!(pokeapi.hits(), 1);
+}
+
+#[tokio::test]
+async fn xor_cube_config_is_used() {
+    let router = router_with(XorCubeConfig { max_numbers: 2, ..Default::default() }, PokeApiConfig::default());
+    assert_eq!(get(router.clone(), "/1/4/8").await.text(), "1728");
+    assert_eq!(get(router, "/1/4/8/1").await.status, 400);
+
+    let config = XorCubeConfig::from_settings(Some("2"), Some(" 128 ")).unwrap();
+    assert_eq!((config.max_numbers, config.max_exponent), (2, 128));
+    let config = XorCubeConfig::from_settings(None, None).unwrap();
+    assert_eq!((config.max_numbers, config.max_exponent), (20, 64));
+    assert!(XorCubeConfig::from_settings(Some("0"), None).is_err());
+    assert!(XorCubeConfig::from_settings(Some("many"), None).is_err());
+    assert!(XorCubeConfig::from_settings(None, Some("-1")).is_err());
+}
+
+#[tokio::test]
+async fn extractor_rejections_are_problem_documents() {
+    let router = router();
+    let cases = [
+        (post(router.clone(), "/7/plan", "application/json", "{").await, 400, "bad_request"),
+        (post(router.clone(), "/7/plan", "text/plain", "{}").await, 415, "unsupported_media_type"),
+        (post_json(router.clone(), "/7/plan", json!({ "recipes": 1 })).await, 422, "unprocessable_entity"),
+        (post_json(router.clone(), "/7/issue?kind=bogus", json!({})).await, 400, "bad_request"),
+        (get(router.clone(), "/18/regions/top_list/many").await, 400, "bad_request"),
+        (post(router, "/11/red_pixels", "multipart/form-data", "").await, 400, "bad_request"),
+    ];
+    for (response, status, code) in cases {
+        assert_eq!(response.status, status, "{}", response.text());
+        assert_eq!(response.error_code(), code);
+    }
+}
@@ -0,0 +1,97 @@
+//! Local stand-ins for the upstream APIs used by days 8 and 21.
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::common::spawn_server;
+
+pub struct StandIn {
+    pub base_url: String,
+    hits: Arc<AtomicUsize>,
+}
+
+impl StandIn {
+    /// Number of requests served so far.
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+}
+
+async fn pokemon(
+    State(hits): State<Arc<AtomicUsize>>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    hits.fetch_add(1, Ordering::SeqCst);
+    // Slow enough that concurrent lookups overlap.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    match id.as_str() {
+        "1" | "bulbasaur" => Ok(Json(json!({ "id": 1, "name": "bulbasaur", "weight": 69, "height": 7 }))),
+        "25" | "pikachu" => Ok(Json(json!({ "id": 25, "name": "pikachu", "weight": 60, "height": 4 }))),
+        "143" | "snorlax" => Ok(Json(json!({ "id": 143, "name": "snorlax", "weight": 4600, "height": 21 }))),
+        _ => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Serves `/api/v2/pokemon/:id/` for bulbasaur (1), pikachu (25) and snorlax (143).
+pub async fn pokeapi() -> StandIn {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let router = Router::new()
+        .route("/api/v2/pokemon/:id/", get(pokemon))
+        .with_state(hits.clone());
+    let addr = spawn_server(router).await;
+    StandIn { base_url: format!("http://{}", addr), hits }
+}
+
+#[derive(Deserialize)]
+struct ReverseQuery {
+    lat: f64,
+    lon: f64,
+}
+
+// Answers with a single feature in a country chosen by hemisphere.
+async fn reverse(
+    State(hits): State<Arc<AtomicUsize>>,
+    Query(query): Query<ReverseQuery>,
+) -> Json<Value> {
+    hits.fetch_add(1, Ordering::SeqCst);
+    let country = if query.lat < 0.0 { "Brazil" } else { "Iceland" };
+    Json(json!({
+        "type": "FeatureCollection",
+        "features": [{
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [query.lon, query.lat] },
+            "properties": {
+                "osm_id": 1,
+                "osm_type": "R",
+                "osm_key": "place",
+                "osm_value": "country",
+                "type": "country",
+                "name": country,
+                "country": country,
+            }
+        }]
+    }))
+}
+
+/// Serves Photon's `/reverse` endpoint.
+pub async fn photon() -> StandIn {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let router = Router::new()
+        .route("/reverse", get(reverse))
+        .with_state(hits.clone());
+    let addr = spawn_server(router).await;
+    StandIn { base_url: format!("http://{}", addr), hits }
+}